@@ -1,22 +1,66 @@
-use std::env;
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use askama::Template;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{Html, IntoResponse},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
 use tower_http::services::ServeDir;
 
+#[derive(Clone)]
+struct AppState {
+    api_client: reqwest::Client,
+    jwks_url: String,
+    introspection_url: String,
+    // bearer token expected by the auth service on introspection requests
+    introspection_api_key: String,
+    // public keys of the auth service, refreshed when a token refers to a
+    // key we don't know yet (ie: after a key rotation)
+    jwks: Arc<RwLock<JwksCache>>,
+    // expected `iss` and `aud` claims of the tokens
+    jwt_issuer: String,
+    jwt_audience: String,
+}
+
 #[tokio::main]
 async fn main() {
+    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+    let introspection_api_key = env::var("INTROSPECTION_API_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .expect("INTROSPECTION_API_KEY must be set, to the same value as on the auth service");
+    let state = AppState {
+        api_client: reqwest::Client::builder()
+            .timeout(AUTH_SERVICE_TIMEOUT)
+            .build()
+            .unwrap(),
+        jwks_url: format!("http://{}:3000/.well-known/jwks.json", auth_hostname),
+        introspection_url: format!("http://{}:3000/introspect", auth_hostname),
+        introspection_api_key,
+        jwks: Arc::new(RwLock::new(JwksCache {
+            keys: JwkSet { keys: vec![] },
+            fetched_at: None,
+        })),
+        jwt_issuer: env::var("JWT_ISSUER").unwrap_or("auth-service".to_owned()),
+        jwt_audience: env::var("JWT_AUDIENCE").unwrap_or("app-service".to_owned()),
+    };
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
@@ -46,7 +90,7 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
+async fn protected(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
         None => {
@@ -54,35 +98,140 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
         }
     };
 
-    let api_client = reqwest::Client::builder().build().unwrap();
+    match verify_token(jwt_cookie.value(), &state).await {
+        Ok(email) => Json(ProtectedRouteResponse {
+            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+            email,
+        })
+        .into_response(),
+        Err(VerifyTokenError::InvalidToken) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(VerifyTokenError::AuthServiceUnavailable) => {
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
 
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
+enum VerifyTokenError {
+    InvalidToken,
+    AuthServiceUnavailable,
+}
 
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+const AUTH_SERVICE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tokens are first checked locally against the public keys published by the
+// auth service, so forged or expired ones don't cost a round trip. Only the
+// auth service knows whether a token was revoked (eg: on logout), so the
+// others are then introspected. Returns the email of the user.
+async fn verify_token(token: &str, state: &AppState) -> Result<String, VerifyTokenError> {
+    verify_signature(token, state).await?;
+    introspect(token, state).await
+}
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+async fn verify_signature(token: &str, state: &AppState) -> Result<(), VerifyTokenError> {
+    let kid = decode_header(token)
+        .ok()
+        .and_then(|header| header.kid)
+        .ok_or(VerifyTokenError::InvalidToken)?;
+
+    let decoding_key = match find_decoding_key(&kid, state).await {
+        Some(key) => key,
+        None => {
+            refresh_jwks(state).await?;
+            find_decoding_key(&kid, state)
+                .await
+                .ok_or(VerifyTokenError::InvalidToken)?
         }
     };
 
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
-        }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
-        })
-        .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_issuer(&[&state.jwt_issuer]);
+    validation.set_audience(&[&state.jwt_audience]);
+    validation.validate_nbf = true;
+
+    // what the token says is taken from the introspection response
+    decode::<serde_json::Value>(token, &decoding_key, &validation)
+        .map(|_| ())
+        .map_err(|_| VerifyTokenError::InvalidToken)
+}
+
+// RFC 7662 response of the auth service, of which we only need these
+#[derive(Debug, Deserialize)]
+struct IntrospectResponse {
+    active: bool,
+    sub: Option<String>,
+}
+
+async fn introspect(token: &str, state: &AppState) -> Result<String, VerifyTokenError> {
+    let response = state
+        .api_client
+        .post(&state.introspection_url)
+        .bearer_auth(&state.introspection_api_key)
+        .form(&[("token", token)])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|_| VerifyTokenError::AuthServiceUnavailable)?
+        .json::<IntrospectResponse>()
+        .await
+        .map_err(|_| VerifyTokenError::AuthServiceUnavailable)?;
+
+    match response {
+        IntrospectResponse {
+            active: true,
+            sub: Some(sub),
+        } => Ok(sub),
+        _ => Err(VerifyTokenError::InvalidToken),
+    }
+}
+
+// Tokens with unknown key IDs are cheap to forge: they trigger at most one
+// refetch per interval, and are otherwise answered from the cache.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+struct JwksCache {
+    keys: JwkSet,
+    fetched_at: Option<Instant>,
+}
+
+impl JwksCache {
+    fn can_refresh(&self) -> bool {
+        self.fetched_at
+            .is_none_or(|fetched_at| fetched_at.elapsed() >= JWKS_REFRESH_INTERVAL)
+    }
+}
+
+async fn find_decoding_key(kid: &str, state: &AppState) -> Option<DecodingKey> {
+    let jwks = state.jwks.read().await;
+    jwks.keys
+        .find(kid)
+        .and_then(|jwk| DecodingKey::from_jwk(jwk).ok())
+}
+
+async fn refresh_jwks(state: &AppState) -> Result<(), VerifyTokenError> {
+    // held during the fetch, so that concurrent misses wait for its outcome
+    // instead of fetching again
+    let mut jwks = state.jwks.write().await;
+    if !jwks.can_refresh() {
+        return Ok(());
     }
+    jwks.fetched_at = Some(Instant::now());
+
+    jwks.keys = state
+        .api_client
+        .get(&state.jwks_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|_| VerifyTokenError::AuthServiceUnavailable)?
+        .json::<JwkSet>()
+        .await
+        .map_err(|_| VerifyTokenError::AuthServiceUnavailable)?;
+
+    Ok(())
 }
 
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub email: String,
 }
@@ -104,7 +104,16 @@ signupButton.addEventListener("click", (e) => {
             signupForm.password.value = "";
             signupForm.twoFA.checked = false;
             signupErrAlter.style.display = "none";
-            alert("You have successfully created a user.");
+            response.json().then(data => {
+                if (data.recoveryCodes !== undefined) {
+                    alert("You have successfully created a user.\n\n"
+                        + "Keep these recovery codes somewhere safe, each of them can be used once "
+                        + "to log in if you can't receive your 2FA code:\n\n"
+                        + data.recoveryCodes.join("\n"));
+                } else {
+                    alert("You have successfully created a user.");
+                }
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -122,6 +131,9 @@ signupButton.addEventListener("click", (e) => {
     });
 });
 
+// Warn users when they are about to run out of recovery codes
+const LOW_RECOVERY_CODES_THRESHOLD = 3;
+
 const TwoFAForm = document.getElementById("2fa-form");
 const TwoFAButton = document.getElementById("2fa-form-submit");
 const TwoFAErrAlter = document.getElementById("2fa-err-alert");
@@ -132,20 +144,34 @@ TwoFAButton.addEventListener("click", (e) => {
     const email = TwoFAForm.email.value;
     const loginAttemptId = TwoFAForm.login_attempt_id.value;
     const TwoFACode = TwoFAForm.email_code.value;
+    const recoveryCode = TwoFAForm.recovery_code.value;
+
+    const body = recoveryCode !== ""
+        ? { email, loginAttemptId, recoveryCode }
+        : { email, loginAttemptId, "2FACode": TwoFACode };
 
     fetch('/verify-2fa', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json',
         },
-        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
+        body: JSON.stringify(body),
     }).then(response => {
         if (response.ok) {
             TwoFAForm.email.value = "";
             TwoFAForm.email_code.value = "";
+            TwoFAForm.recovery_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            response.json().then(data => {
+                const remaining = data.remainingRecoveryCodes;
+                if (remaining <= LOW_RECOVERY_CODES_THRESHOLD) {
+                    alert("You have successfully logged in.\n\n"
+                        + `Warning: only ${remaining} recovery code(s) left, please generate new ones.`);
+                } else {
+                    alert("You have successfully logged in.");
+                }
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
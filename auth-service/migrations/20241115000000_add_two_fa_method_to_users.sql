@@ -0,0 +1,9 @@
+-- The second factor is no longer a simple flag: a user may use emailed codes
+-- or an authenticator app (TOTP), in which case its secret is stored as well.
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'none';
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN pending_totp_secret TEXT;
+
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+
+ALTER TABLE users DROP COLUMN requires_2fa;
@@ -0,0 +1,5 @@
+-- Users now have to prove they own their email address. Accounts created
+-- before that are considered verified, so as not to lock anyone out.
+ALTER TABLE users ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET verified = TRUE;
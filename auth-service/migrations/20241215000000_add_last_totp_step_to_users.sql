@@ -0,0 +1,3 @@
+-- TOTP codes can only be used once: the time step of the last one accepted
+-- is kept, and codes of that step or earlier are rejected.
+ALTER TABLE users ADD COLUMN last_totp_step INTEGER;
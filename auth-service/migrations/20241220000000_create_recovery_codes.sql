@@ -0,0 +1,7 @@
+-- Recovery codes are kept alongside the users they belong to, so they
+-- survive a restart as well. Only their SHA-256 hashes are stored.
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    email TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (email, code_hash)
+);
@@ -1,30 +1,67 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{OnceCell, RwLock};
 
 use crate::{
     domain::{
-        data_stores::{token::BannedTokenStore, twofa::TwoFACodeStore, user::UserStore},
+        audit::AuditSink,
+        clock::Clock,
+        data_stores::{
+            failed_login::FailedLoginStore,
+            one_time::OneTimeTokenStore,
+            recovery::RecoveryCodeStore,
+            refresh::RefreshTokenStore,
+            session::SessionStore,
+            token::BannedTokenStore,
+            twofa::TwoFACodeStore,
+            user::{UserStore, UserStoreError},
+        },
+        user::HashedPassword,
         EmailClient,
     },
     services::{
         hashmap_banned_token_store::HashmapBannedTokenStore,
+        hashmap_failed_login_store::HashmapFailedLoginStore,
+        hashmap_one_time_token_store::HashmapOneTimeTokenStore,
+        hashmap_recovery_code_store::HashmapRecoveryCodeStore,
+        hashmap_refresh_token_store::HashmapRefreshTokenStore,
+        hashmap_session_store::HashmapSessionStore,
         hashmap_two_fa_code_store::HashmapTwoFACodeStore, hashmap_user_store::HashmapUserStore,
-        mock_email_client::MockEmailClient,
+        json_lines_audit_sink::JsonLinesAuditSink, mock_email_client::MockEmailClient,
+        system_clock::SystemClock,
     },
-    utils::ThreadSafe,
+    utils::{keys::Keyring, settings::AppSettings, ThreadSafe},
 };
 
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore>>;
 pub type UserStoreType = Arc<RwLock<dyn UserStore>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore>>;
+pub type OneTimeTokenStoreType = Arc<RwLock<dyn OneTimeTokenStore>>;
+pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore>>;
+pub type AuditSinkType = Arc<RwLock<dyn AuditSink>>;
 pub type EmailClientType = Arc<RwLock<dyn EmailClient>>;
+pub type ClockType = Arc<RwLock<dyn Clock>>;
+pub type KeyringType = Arc<RwLock<Keyring>>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub one_time_token_store: OneTimeTokenStoreType,
+    pub failed_login_store: FailedLoginStoreType,
+    pub audit_sink: AuditSinkType,
     pub email_client: EmailClientType,
+    pub clock: ClockType,
+    pub keyring: KeyringType,
+    pub settings: Arc<AppSettings>,
+    // made with the password hashing settings: computed again when they change
+    dummy_password_hash: Arc<OnceCell<HashedPassword>>,
 }
 
 impl AppState {
@@ -43,10 +80,64 @@ impl AppState {
         self
     }
 
+    pub fn recovery_code_store(mut self, recovery_code_store: RecoveryCodeStoreType) -> Self {
+        self.recovery_code_store = recovery_code_store;
+        self
+    }
+
+    pub fn refresh_token_store(mut self, refresh_token_store: RefreshTokenStoreType) -> Self {
+        self.refresh_token_store = refresh_token_store;
+        self
+    }
+
+    pub fn session_store(mut self, session_store: SessionStoreType) -> Self {
+        self.session_store = session_store;
+        self
+    }
+
+    pub fn one_time_token_store(mut self, one_time_token_store: OneTimeTokenStoreType) -> Self {
+        self.one_time_token_store = one_time_token_store;
+        self
+    }
+
+    pub fn failed_login_store(mut self, failed_login_store: FailedLoginStoreType) -> Self {
+        self.failed_login_store = failed_login_store;
+        self
+    }
+
+    pub fn audit_sink(mut self, audit_sink: AuditSinkType) -> Self {
+        self.audit_sink = audit_sink;
+        self
+    }
+
     pub fn email_client(mut self, email_client: EmailClientType) -> Self {
         self.email_client = email_client;
         self
     }
+
+    pub fn clock(mut self, clock: ClockType) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn keyring(mut self, keyring: KeyringType) -> Self {
+        self.keyring = keyring;
+        self
+    }
+
+    pub fn settings(mut self, settings: AppSettings) -> Self {
+        self.settings = Arc::new(settings);
+        self.dummy_password_hash = Arc::default();
+        self
+    }
+
+    /// Hash the passwords of unknown users are checked against, see
+    /// `HashedPassword::dummy`. Computed once, on startup.
+    pub async fn dummy_password_hash(&self) -> Result<&HashedPassword, UserStoreError> {
+        self.dummy_password_hash
+            .get_or_try_init(|| HashedPassword::dummy(self.settings.password_hashing))
+            .await
+    }
 }
 
 impl Default for AppState {
@@ -55,7 +146,42 @@ impl Default for AppState {
             user_store: HashmapUserStore::thread_safe(),
             banned_token_store: HashmapBannedTokenStore::thread_safe(),
             two_fa_code_store: HashmapTwoFACodeStore::thread_safe(),
+            recovery_code_store: HashmapRecoveryCodeStore::thread_safe(),
+            refresh_token_store: HashmapRefreshTokenStore::thread_safe(),
+            session_store: HashmapSessionStore::thread_safe(),
+            one_time_token_store: HashmapOneTimeTokenStore::thread_safe(),
+            failed_login_store: HashmapFailedLoginStore::thread_safe(),
+            audit_sink: JsonLinesAuditSink::thread_safe(),
             email_client: MockEmailClient::thread_safe(),
+            clock: SystemClock::thread_safe(),
+            keyring: Keyring::thread_safe(),
+            settings: Arc::new(AppSettings::default()),
+            dummy_password_hash: Arc::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::user::PasswordHashingParams;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_hash_the_dummy_password_once_per_settings() {
+        let state = AppState::default();
+        let dummy = state.dummy_password_hash().await.unwrap().clone();
+        assert_eq!(state.dummy_password_hash().await.unwrap(), &dummy);
+
+        let params = PasswordHashingParams {
+            iterations: PasswordHashingParams::default().iterations + 1,
+            ..PasswordHashingParams::default()
+        };
+        let state = state.settings(AppSettings {
+            password_hashing: params,
+            ..AppSettings::default()
+        });
+        let dummy = state.dummy_password_hash().await.unwrap();
+        assert!(!dummy.needs_rehash(params));
+    }
+}
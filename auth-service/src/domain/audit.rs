@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::user::Email;
+
+/// Something that happened to an account, worth keeping a record of: who did
+/// it, from where and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: AuditEventKind,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditEventKind) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            kind,
+            email: None,
+            ip: None,
+            user_agent: None,
+        }
+    }
+
+    pub fn email(mut self, email: &Email) -> Self {
+        self.email = Some(email.as_ref().to_owned());
+        self
+    }
+
+    pub fn client(mut self, ip: Option<String>, user_agent: Option<String>) -> Self {
+        self.ip = ip;
+        self.user_agent = user_agent;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum AuditEventKind {
+    #[serde(rename = "signup")]
+    Signup,
+    /// A session was started, after the second factor if the user has one
+    #[serde(rename = "login_succeeded")]
+    LoginSucceeded,
+    #[serde(rename = "login_failed")]
+    LoginFailed { reason: LoginFailure },
+    /// A 2FA code was emailed to the user
+    #[serde(rename = "two_fa_code_sent")]
+    TwoFACodeSent,
+    #[serde(rename = "two_fa_verified")]
+    TwoFAVerified,
+    #[serde(rename = "two_fa_failed")]
+    TwoFAFailed,
+    /// The user confirmed a TOTP secret, which is now their second factor
+    #[serde(rename = "two_fa_enabled")]
+    TwoFAEnabled,
+    #[serde(rename = "logout")]
+    Logout {
+        #[serde(rename = "allSessions")]
+        all_sessions: bool,
+    },
+    /// A JWT or refresh token was presented but is invalid, expired or
+    /// revoked
+    #[serde(rename = "token_rejected")]
+    TokenRejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginFailure {
+    /// Unknown user or wrong password, told apart in the log only
+    UnknownUser,
+    WrongPassword,
+    Locked,
+    EmailNotVerified,
+}
+
+/// Where audit events are written to. Events are only ever appended.
+#[async_trait::async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn record(&mut self, event: &AuditEvent) -> Result<(), AuditSinkError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditSinkError {
+    UnexpectedError,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn should_serialize_the_kind_along_with_the_event() {
+        let event = AuditEvent::new(AuditEventKind::LoginFailed {
+            reason: LoginFailure::WrongPassword,
+        })
+        .email(&Email::default())
+        .client(Some("127.0.0.1".into()), None);
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["event"], json!("login_failed"));
+        assert_eq!(value["reason"], json!("wrong_password"));
+        assert_eq!(value["email"], json!("email@email.com"));
+        assert_eq!(value["ip"], json!("127.0.0.1"));
+        assert_eq!(value["userAgent"], json!(null));
+
+        assert_eq!(serde_json::from_value::<AuditEvent>(value).unwrap(), event);
+    }
+}
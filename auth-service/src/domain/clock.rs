@@ -0,0 +1,7 @@
+use chrono::{DateTime, Utc};
+
+/// Source of the current time, so that time-dependent logic (eg: TOTP codes)
+/// can be driven deterministically in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
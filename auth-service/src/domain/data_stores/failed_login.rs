@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::domain::user::Email;
+
+// Wrong passwords tolerated for an account before it gets locked
+pub const MAX_FAILED_LOGINS_PER_ACCOUNT: u32 = 5;
+
+// Higher for an address, which may be shared by many users behind a NAT
+pub const MAX_FAILED_LOGINS_PER_IP: u32 = 20;
+
+// First lockout, doubled on each further failure up to the maximum
+pub const LOGIN_LOCKOUT_BASE_SECONDS: i64 = 30;
+pub const LOGIN_LOCKOUT_MAX_SECONDS: i64 = 60 * 60; // 1 hour
+
+// Failures are forgotten once none happened for that long
+pub const FAILED_LOGINS_RESET_AFTER_SECONDS: i64 = 60 * 60 * 24; // 1 day
+
+/// What failed logins are counted against: the account whose password was
+/// tried, or the address the attempts came from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FailedLoginKey {
+    Account(Email),
+    Ip(String),
+}
+
+/// When failed logins lead to a lockout, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    pub reset_after: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn new(max_failures: u32) -> Self {
+        Self {
+            max_failures,
+            base_lockout: Duration::try_seconds(LOGIN_LOCKOUT_BASE_SECONDS)
+                .expect("valid base lockout"),
+            max_lockout: Duration::try_seconds(LOGIN_LOCKOUT_MAX_SECONDS)
+                .expect("valid max lockout"),
+            reset_after: Duration::try_seconds(FAILED_LOGINS_RESET_AFTER_SECONDS)
+                .expect("valid failed logins reset delay"),
+        }
+    }
+
+    pub fn per_account() -> Self {
+        Self::new(MAX_FAILED_LOGINS_PER_ACCOUNT)
+    }
+
+    pub fn per_ip() -> Self {
+        Self::new(MAX_FAILED_LOGINS_PER_IP)
+    }
+
+    /// How long to lock after the given number of consecutive failures, if
+    /// at all: the base lockout once the limit is reached, doubled with each
+    /// failure beyond it.
+    pub fn lockout_after(&self, failures: u32) -> Option<Duration> {
+        let beyond_limit = failures.checked_sub(self.max_failures)?;
+        let lockout = 2i32
+            .checked_pow(beyond_limit)
+            .and_then(|factor| self.base_lockout.checked_mul(factor))
+            .unwrap_or(self.max_lockout);
+
+        Some(lockout.min(self.max_lockout))
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FailedLoginStore: Send + Sync {
+    /// Until when logins are refused for the key, if they currently are.
+    async fn locked_until(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<DateTime<Utc>>, FailedLoginStoreError>;
+    /// Counts a wrong password against the key, locking it if that was one
+    /// too many. Returns until when it is now locked, if it is.
+    async fn record_failure(
+        &mut self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<DateTime<Utc>>, FailedLoginStoreError>;
+    /// Forgets the failures of the key, after a successful login.
+    async fn reset(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FailedLoginStoreError {
+    UnexpectedError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_not_lock_below_the_limit() {
+        let policy = LockoutPolicy::new(3);
+
+        assert_eq!(policy.lockout_after(0), None);
+        assert_eq!(policy.lockout_after(2), None);
+        assert_eq!(policy.lockout_after(3), Some(policy.base_lockout));
+    }
+
+    #[test]
+    fn should_double_the_lockout_up_to_the_maximum() {
+        let policy = LockoutPolicy::new(3);
+
+        assert_eq!(policy.lockout_after(4), Some(policy.base_lockout * 2));
+        assert_eq!(policy.lockout_after(5), Some(policy.base_lockout * 4));
+        assert_eq!(policy.lockout_after(10), Some(policy.max_lockout));
+        assert_eq!(policy.lockout_after(u32::MAX), Some(policy.max_lockout));
+    }
+}
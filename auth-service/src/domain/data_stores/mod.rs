@@ -1,3 +1,8 @@
+pub mod failed_login;
+pub mod one_time;
+pub mod recovery;
+pub mod refresh;
+pub mod session;
 pub mod token;
 pub mod twofa;
 pub mod user;
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{domain::user::Email, utils::redact::redacted_debug};
+
+// How long a password reset link can be followed after being emailed (1 hour)
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 60 * 60;
+// Same for the link verifying the email address, sent at signup (1 day)
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24;
+// Minimum time between two verification emails sent on request
+pub const EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS: i64 = 60;
+
+// 256 bits of randomness, hex encoded
+const ONE_TIME_TOKEN_BYTES: usize = 32;
+
+/// What a `OneTimeToken` lets its holder do. A token issued for one purpose
+/// can't be used for another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OneTimeTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl OneTimeTokenPurpose {
+    pub fn ttl(&self) -> Duration {
+        let seconds = match self {
+            Self::PasswordReset => PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            Self::EmailVerification => EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+        };
+
+        Duration::try_seconds(seconds).expect("valid one-time token TTL")
+    }
+}
+
+#[async_trait::async_trait]
+pub trait OneTimeTokenStore: Send + Sync {
+    /// Issues `token` to the user for `purpose`, valid until `expires_at`.
+    /// Replaces any token they were previously issued for it: only the
+    /// latest email sent is valid.
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        purpose: OneTimeTokenPurpose,
+        token: &OneTimeToken,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), OneTimeTokenStoreError>;
+    /// Checks and removes the token in one go, so it can't be used twice.
+    /// Returns the user it was issued to.
+    async fn consume_token(
+        &mut self,
+        purpose: OneTimeTokenPurpose,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError>;
+    /// When the user was last issued a token for `purpose`, if they have
+    /// one that wasn't consumed yet.
+    async fn last_issued_at(
+        &self,
+        email: &Email,
+        purpose: OneTimeTokenPurpose,
+    ) -> Result<Option<DateTime<Utc>>, OneTimeTokenStoreError>;
+    /// Drops every token of the user, whatever their purpose.
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), OneTimeTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OneTimeTokenStoreError {
+    TokenNotFound,
+    TokenExpired,
+    UnexpectedError,
+}
+
+/// Opaque, short lived token sent by email, proving its holder has access to
+/// the mailbox (eg: to reset their password).
+#[derive(Clone, PartialEq)]
+pub struct OneTimeToken(String);
+
+redacted_debug!(OneTimeToken);
+
+impl OneTimeToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == ONE_TIME_TOKEN_BYTES * 2 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(token.to_ascii_lowercase()))
+        } else {
+            Err("Invalid one-time token".into())
+        }
+    }
+
+    /// Generates a token from the operating system's CSPRNG.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; ONE_TIME_TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+
+        Self(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    /// Stores only keep this hash, so a leak of their content doesn't give
+    /// away usable tokens.
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for OneTimeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_generate_distinct_parsable_tokens() {
+        let first = OneTimeToken::generate();
+        let second = OneTimeToken::generate();
+
+        assert_ne!(first, second);
+        assert_eq!(first.as_ref().len(), ONE_TIME_TOKEN_BYTES * 2);
+        assert_eq!(OneTimeToken::parse(first.as_ref().to_owned()), Ok(first));
+    }
+
+    #[test]
+    fn should_reject_malformed_tokens() {
+        assert!(OneTimeToken::parse("".into()).is_err());
+        assert!(OneTimeToken::parse("not a token".into()).is_err());
+        assert!(OneTimeToken::parse("ab".repeat(ONE_TIME_TOKEN_BYTES - 1)).is_err());
+        assert!(OneTimeToken::parse("zz".repeat(ONE_TIME_TOKEN_BYTES)).is_err());
+    }
+
+    #[test]
+    fn should_hash_tokens() {
+        let token = OneTimeToken::generate();
+
+        assert_eq!(token.hash().len(), 64);
+        assert_ne!(token.hash(), token.as_ref());
+        assert_eq!(token.hash(), token.clone().hash());
+    }
+}
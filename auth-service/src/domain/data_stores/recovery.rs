@@ -0,0 +1,180 @@
+use rand::{rngs::OsRng, CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::{domain::user::Email, utils::redact::redacted_debug};
+
+// Number of codes handed out at once
+pub const RECOVERY_CODES_COUNT: usize = 10;
+
+// Unambiguous characters only (no 0/o, 1/l/i), so codes can be copied by hand
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+// 10 characters out of 31, ~49 bits of entropy each
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore: Send + Sync {
+    /// Replaces every code of the user (used or not) with the given ones.
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<HashedRecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    /// Checks and removes the code in one go, so it can't be used twice.
+    /// Returns the number of codes the user has left.
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<usize, RecoveryCodeStoreError>;
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+    async fn remove_codes(&mut self, email: &Email) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    InvalidCode,
+    UnexpectedError,
+}
+
+/// Single-use code letting a user log in without their usual second factor,
+/// formatted as `xxxxx-xxxxx`.
+#[derive(Clone, PartialEq)]
+pub struct RecoveryCode(String);
+
+redacted_debug!(RecoveryCode);
+
+impl RecoveryCode {
+    /// Accepts codes regardless of case, spacing or dashes, as they are
+    /// likely to be typed in from a printout.
+    pub fn parse(code: String) -> Result<Self, String> {
+        let normalized: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if normalized.len() != RECOVERY_CODE_LENGTH
+            || !normalized
+                .bytes()
+                .all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+        {
+            return Err(format!("Invalid recovery code. Got: \"{code}\""));
+        }
+
+        Ok(Self(format_recovery_code(&normalized)))
+    }
+
+    /// Generates a code from the operating system's CSPRNG.
+    pub fn generate() -> Self {
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    pub fn generate_with_rng<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        let code: String = (0..RECOVERY_CODE_LENGTH)
+            .map(|_| {
+                let index = rng.gen_range(0..RECOVERY_CODE_ALPHABET.len());
+                char::from(RECOVERY_CODE_ALPHABET[index])
+            })
+            .collect();
+
+        Self(format_recovery_code(&code))
+    }
+
+    /// Generates a fresh batch of `RECOVERY_CODES_COUNT` codes.
+    pub fn generate_batch() -> Vec<Self> {
+        (0..RECOVERY_CODES_COUNT)
+            .map(|_| Self::generate())
+            .collect()
+    }
+
+    pub fn hash(&self) -> HashedRecoveryCode {
+        HashedRecoveryCode(format!("{:x}", Sha256::digest(self.0.as_bytes())))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// SHA-256 of a `RecoveryCode`, hex encoded. Unlike passwords, the codes are
+/// random with enough entropy that a fast, unsalted hash is sufficient.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct HashedRecoveryCode(String);
+
+redacted_debug!(HashedRecoveryCode);
+
+impl AsRef<str> for HashedRecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn format_recovery_code(code: &str) -> String {
+    let (first, second) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+    format!("{first}-{second}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn should_generate_well_formed_codes() {
+        let code = RecoveryCode::generate();
+
+        assert_eq!(code.as_ref().len(), RECOVERY_CODE_LENGTH + 1);
+        assert_eq!(code.as_ref().chars().nth(5), Some('-'));
+        assert_eq!(RecoveryCode::parse(code.as_ref().to_owned()), Ok(code));
+    }
+
+    #[test]
+    fn should_generate_distinct_codes_in_batch() {
+        let codes = RecoveryCode::generate_batch();
+        let unique: HashSet<_> = codes.iter().map(|code| code.as_ref()).collect();
+
+        assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+        assert_eq!(unique.len(), RECOVERY_CODES_COUNT);
+    }
+
+    #[test]
+    fn should_normalize_codes_when_parsing() {
+        let expected = RecoveryCode::parse("abcde-fghjk".into()).unwrap();
+
+        assert_eq!(
+            RecoveryCode::parse("ABCDE-FGHJK".into()),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            RecoveryCode::parse("abcdefghjk".into()),
+            Ok(expected.clone())
+        );
+        assert_eq!(RecoveryCode::parse(" abcde fghjk ".into()), Ok(expected));
+    }
+
+    #[test]
+    fn should_reject_malformed_codes() {
+        assert!(RecoveryCode::parse("".into()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghj".into()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghjkm".into()).is_err());
+        // ambiguous characters are not part of the alphabet
+        assert!(RecoveryCode::parse("abcde-fghj0".into()).is_err());
+        assert!(RecoveryCode::parse("123456".into()).is_err());
+    }
+
+    #[test]
+    fn should_hash_codes_with_sha256() {
+        let code = RecoveryCode::parse("abcde-fghjk".into()).unwrap();
+        let hash = code.hash();
+
+        assert_eq!(hash.as_ref().len(), 64);
+        assert_ne!(hash.as_ref(), code.as_ref());
+        assert_eq!(
+            hash,
+            RecoveryCode::parse("ABCDEFGHJK".into()).unwrap().hash()
+        );
+    }
+}
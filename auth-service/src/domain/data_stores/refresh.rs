@@ -0,0 +1,142 @@
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{domain::user::Email, utils::redact::redacted_debug};
+
+// How long a refresh token can be used after being issued. Each rotation
+// issues a new token, so an active session never expires.
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 30; // 30 days
+
+// 256 bits of randomness, hex encoded
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    /// Starts a new family (ie: a new session) with `token` as first token.
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        family_id: &RefreshTokenFamilyId,
+        token: &RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError>;
+    /// Marks `token` as used and adds `new_token` to the same family, in one
+    /// go. Presenting an already used token revokes its whole family and
+    /// returns `TokenReused`: either the legitimate user or an attacker holds
+    /// a stolen token, and we can't tell which.
+    async fn rotate(
+        &mut self,
+        token: &RefreshToken,
+        new_token: &RefreshToken,
+    ) -> Result<(Email, RefreshTokenFamilyId), RefreshTokenStoreError>;
+    /// Revokes the family `token` belongs to (eg: on logout).
+    async fn revoke(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+    /// Revokes every token of the family, ie: ends its session.
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError>;
+    /// Revokes every token of the user, whatever their session.
+    async fn revoke_all(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    TokenExpired,
+    TokenReused,
+    UnexpectedError,
+}
+
+/// Opaque, long lived token exchanged for a new JWT (and a new refresh
+/// token) once the JWT expired.
+#[derive(Clone, PartialEq)]
+pub struct RefreshToken(String);
+
+redacted_debug!(RefreshToken);
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == REFRESH_TOKEN_BYTES * 2 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(token.to_ascii_lowercase()))
+        } else {
+            Err("Invalid refresh token".into())
+        }
+    }
+
+    /// Generates a token from the operating system's CSPRNG.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; REFRESH_TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+
+        Self(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    /// Stores only keep this hash, so a leak of their content doesn't give
+    /// away usable tokens.
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Identifies the chain of refresh tokens descending from a single login.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RefreshTokenFamilyId(String);
+
+impl RefreshTokenFamilyId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        Uuid::parse_str(&id)
+            .and(Ok(Self(id)))
+            .map_err(|e| format!("Invalid refresh token family ID. Details: {e:?}"))
+    }
+}
+
+impl Default for RefreshTokenFamilyId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for RefreshTokenFamilyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_generate_distinct_parsable_tokens() {
+        let first = RefreshToken::generate();
+        let second = RefreshToken::generate();
+
+        assert_ne!(first, second);
+        assert_eq!(first.as_ref().len(), REFRESH_TOKEN_BYTES * 2);
+        assert_eq!(RefreshToken::parse(first.as_ref().to_owned()), Ok(first));
+    }
+
+    #[test]
+    fn should_reject_malformed_tokens() {
+        assert!(RefreshToken::parse("".into()).is_err());
+        assert!(RefreshToken::parse("not a token".into()).is_err());
+        assert!(RefreshToken::parse("ab".repeat(REFRESH_TOKEN_BYTES - 1)).is_err());
+        assert!(RefreshToken::parse("zz".repeat(REFRESH_TOKEN_BYTES)).is_err());
+    }
+
+    #[test]
+    fn should_hash_tokens() {
+        let token = RefreshToken::generate();
+
+        assert_eq!(token.hash().len(), 64);
+        assert_ne!(token.hash(), token.as_ref());
+        assert_eq!(token.hash(), token.clone().hash());
+    }
+}
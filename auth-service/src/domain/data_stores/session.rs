@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+
+use crate::domain::user::Email;
+
+use super::refresh::RefreshTokenFamilyId;
+
+/// A session starts with each login and lasts as long as the refresh tokens
+/// descending from it, so it shares their family's id.
+pub type SessionId = RefreshTokenFamilyId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    pub created_at: DateTime<Utc>,
+    /// Last time a JWT was issued for the session (login or refresh)
+    pub last_seen_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl Session {
+    pub fn new(email: Email, user_agent: Option<String>, ip: Option<String>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: SessionId::default(),
+            email,
+            created_at: now,
+            last_seen_at: now,
+            user_agent,
+            ip,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+/// Registry of the sessions of each user, so that they can see where they're
+/// logged in. Sessions not seen for longer than the refresh token TTL are
+/// gone, like their refresh tokens.
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    /// Records that the session was just used.
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    /// Removes a session of the user. Sessions of other users are reported
+    /// as not found.
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &SessionId,
+    ) -> Result<Session, SessionStoreError>;
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
@@ -1,6 +1,6 @@
 use crate::domain::user::Email;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BannedTokenStoreError {
     UnexpectedError,
 }
@@ -29,7 +29,26 @@ impl BannedTokenState {
     }
 }
 
+pub type BannedTokenStoreResult<T> = Result<T, BannedTokenStoreError>;
+
+#[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    fn add(&mut self, email: &Email, token: &str);
-    fn verify(&self, token: &str) -> BannedTokenState;
+    /// Bans the token identified by `id` until `expires_at` (unix timestamp,
+    /// in seconds), ie: the `exp` claim of the token. Past that point the
+    /// token is rejected on its own and the store is free to forget about it.
+    /// `id` is the `jti` claim of the token, or its `sid` claim to ban all
+    /// the tokens of a session.
+    async fn add(
+        &mut self,
+        email: &Email,
+        id: &str,
+        expires_at: usize,
+    ) -> BannedTokenStoreResult<()>;
+    async fn verify(&self, id: &str) -> BannedTokenStoreResult<BannedTokenState>;
+    /// Number of tokens currently banned (expired ones are not counted).
+    async fn len(&self) -> BannedTokenStoreResult<usize>;
+
+    async fn is_empty(&self) -> BannedTokenStoreResult<bool> {
+        Ok(self.len().await? == 0)
+    }
 }
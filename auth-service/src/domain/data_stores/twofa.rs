@@ -1,7 +1,17 @@
-use rand::seq::SliceRandom;
+use rand::{rngs::OsRng, CryptoRng, Rng};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::domain::user::Email;
+use crate::{domain::user::Email, utils::redact::redacted_debug};
+
+// How long a 2FA code can be used after being sent
+pub const TWO_FA_CODE_TTL_SECONDS: i64 = 600; // 10 minutes
+
+// Number of wrong codes after which the login attempt is invalidated
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+
+// Number of digits of a 2FA code, unless configured otherwise
+pub const DEFAULT_TWO_FA_CODE_LENGTH: usize = 6;
 
 #[async_trait::async_trait]
 pub trait TwoFACodeStore: Send + Sync {
@@ -12,15 +22,21 @@ pub trait TwoFACodeStore: Send + Sync {
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    /// Returns `CodeExpired` once the code outlived its TTL.
     async fn get_code(
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    /// Counts a wrong code against the pending login attempt. Once the limit
+    /// is reached the attempt is dropped and `TooManyAttempts` is returned.
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
+    CodeExpired,
+    TooManyAttempts,
     UnexpectedError,
 }
 
@@ -48,29 +64,53 @@ impl AsRef<str> for LoginAttemptId {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct TwoFACode(String);
 
+redacted_debug!(TwoFACode);
+
 impl TwoFACode {
     pub fn parse(code: String) -> Result<Self, String> {
-        let digits: String = code.chars().filter(|c| c.is_ascii_digit()).collect();
-        if digits.len() == 6 {
+        Self::parse_with_length(code, DEFAULT_TWO_FA_CODE_LENGTH)
+    }
+
+    pub fn parse_with_length(code: String, length: usize) -> Result<Self, String> {
+        if code.len() == length && code.chars().all(|c| c.is_ascii_digit()) {
             Ok(Self(code))
         } else {
             Err(format!(
-                "Invalid 2FA code. Expected a 6 digit string. Got: \"{code}\""
+                "Invalid 2FA code. Expected a {length} digit string. Got: \"{code}\""
             ))
         }
     }
+
+    /// Generates a code from the operating system's CSPRNG.
+    pub fn generate(length: usize) -> Self {
+        Self::generate_with_rng(&mut OsRng, length)
+    }
+
+    /// Each digit is drawn independently and uniformly, so every one of the
+    /// `10^length` codes (leading zeros and repeated digits included) is
+    /// equally likely.
+    pub fn generate_with_rng<R: Rng + CryptoRng>(rng: &mut R, length: usize) -> Self {
+        let code = (0..length)
+            .map(|_| char::from(b'0' + rng.gen_range(0..10u8)))
+            .collect();
+
+        Self(code)
+    }
+
+    /// Whether the candidate is this code. Their digests are compared rather
+    /// than the codes, so that the time taken doesn't tell how many of the
+    /// leading digits were right.
+    pub fn matches(&self, candidate: &TwoFACode) -> bool {
+        Sha256::digest(self.0.as_bytes()) == Sha256::digest(candidate.0.as_bytes())
+    }
 }
 
 impl Default for TwoFACode {
     fn default() -> Self {
-        let mut rng = rand::thread_rng();
-        let mut nums: Vec<i32> = (0..10).collect();
-        nums.shuffle(&mut rng);
-
-        Self(nums.iter().take(6).map(|num| num.to_string()).collect())
+        Self::generate(DEFAULT_TWO_FA_CODE_LENGTH)
     }
 }
 
@@ -79,3 +119,116 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const SAMPLES: usize = 100_000;
+
+    #[test]
+    fn should_match_only_the_same_code() {
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+
+        assert!(code.matches(&TwoFACode::parse("123456".to_owned()).unwrap()));
+        assert!(!code.matches(&TwoFACode::parse("123450".to_owned()).unwrap()));
+    }
+
+    // Pearson's chi-squared statistic of observed counts against a uniform
+    // distribution over `observed.len()` buckets.
+    fn chi_squared(observed: &[usize], samples: usize) -> f64 {
+        let expected = samples as f64 / observed.len() as f64;
+        observed
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    fn sample_codes(length: usize) -> Vec<TwoFACode> {
+        let mut rng = StdRng::seed_from_u64(0x2fa);
+        (0..SAMPLES)
+            .map(|_| TwoFACode::generate_with_rng(&mut rng, length))
+            .collect()
+    }
+
+    #[test]
+    fn should_generate_codes_of_requested_length() {
+        for length in [4, 6, 8, 10] {
+            let code = TwoFACode::generate(length);
+            assert_eq!(code.as_ref().len(), length);
+            assert!(TwoFACode::parse_with_length(code.as_ref().to_owned(), length).is_ok());
+        }
+    }
+
+    #[test]
+    fn should_parse_only_digit_strings_of_expected_length() {
+        assert!(TwoFACode::parse("012345".into()).is_ok());
+        assert!(TwoFACode::parse("000000".into()).is_ok());
+
+        assert!(TwoFACode::parse("12345".into()).is_err());
+        assert!(TwoFACode::parse("1234567".into()).is_err());
+        assert!(TwoFACode::parse("12-34-56".into()).is_err());
+        assert!(TwoFACode::parse("12345a".into()).is_err());
+        assert!(TwoFACode::parse_with_length("1234".into(), 4).is_ok());
+    }
+
+    #[test]
+    fn should_generate_codes_with_repeated_digits() {
+        let codes = sample_codes(DEFAULT_TWO_FA_CODE_LENGTH);
+
+        let with_repeats = codes
+            .iter()
+            .filter(|code| {
+                let mut digits: Vec<char> = code.as_ref().chars().collect();
+                digits.sort_unstable();
+                digits.dedup();
+                digits.len() < DEFAULT_TWO_FA_CODE_LENGTH
+            })
+            .count();
+
+        // 1 - (10 * 9 * 8 * 7 * 6 * 5) / 10^6 = 84.88% of codes repeat a digit
+        let ratio = with_repeats as f64 / SAMPLES as f64;
+        assert!((ratio - 0.8488).abs() < 0.01, "ratio was {ratio}");
+    }
+
+    #[test]
+    fn should_distribute_digits_uniformly_at_every_position() {
+        let codes = sample_codes(DEFAULT_TWO_FA_CODE_LENGTH);
+
+        for position in 0..DEFAULT_TWO_FA_CODE_LENGTH {
+            let mut counts = [0usize; 10];
+            for code in &codes {
+                let digit = code.as_ref().as_bytes()[position] - b'0';
+                counts[digit as usize] += 1;
+            }
+
+            // critical value for 9 degrees of freedom at p = 0.001
+            let statistic = chi_squared(&counts, SAMPLES);
+            assert!(statistic < 27.88, "position {position}: {statistic}");
+        }
+    }
+
+    #[test]
+    fn should_distribute_codes_uniformly_over_the_code_space() {
+        let codes = sample_codes(DEFAULT_TWO_FA_CODE_LENGTH);
+
+        // bucket codes by their leading 3 digits: 1000 buckets of 1000 codes
+        let mut counts = vec![0usize; 1000];
+        for code in &codes {
+            let value: usize = code.as_ref().parse().unwrap();
+            counts[value / 1000] += 1;
+        }
+
+        // critical value for 999 degrees of freedom at p = 0.001
+        let statistic = chi_squared(&counts, SAMPLES);
+        assert!(statistic < 1143.92, "{statistic}");
+
+        // the whole range is reachable, not only codes without repeated digits
+        let smallest = codes.iter().map(|c| c.as_ref()).min().unwrap();
+        let largest = codes.iter().map(|c| c.as_ref()).max().unwrap();
+        assert!(smallest.starts_with("000"));
+        assert!(largest.starts_with("999"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::domain::user::{Email, Password, User};
+use crate::domain::user::{Email, HashedPassword, Password, User};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum UserStoreError {
@@ -12,7 +12,15 @@ pub type UserStoreResult<T> = Result<T, UserStoreError>;
 
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    fn add_user(&mut self, user: User) -> UserStoreResult<()>;
-    fn get_user(&self, email: Email) -> UserStoreResult<User>;
-    fn validate_user(&self, email: Email, password: Password) -> UserStoreResult<()>;
+    async fn add_user(&mut self, user: User) -> UserStoreResult<()>;
+    async fn get_user(&self, email: Email) -> UserStoreResult<User>;
+    async fn validate_user(&self, email: Email, password: Password) -> UserStoreResult<()>;
+    async fn update_password(
+        &mut self,
+        email: Email,
+        password: HashedPassword,
+    ) -> UserStoreResult<()>;
+    /// Replaces the stored user having the same email.
+    async fn update_user(&mut self, user: User) -> UserStoreResult<()>;
+    async fn delete_user(&mut self, email: &Email) -> UserStoreResult<()>;
 }
@@ -5,8 +5,14 @@ pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials(String),
     IncorrectCredentials,
+    TwoFACodeExpired,
+    TooManyTwoFAAttempts,
     MissingToken,
     InvalidToken,
+    SessionNotFound,
+    EmailNotVerified,
+    /// Number of seconds to wait before trying again
+    TooManyRequests(u64),
     GenerateTokenError(GenerateTokenError),
     BadInput(String),
     UnexpectedError,
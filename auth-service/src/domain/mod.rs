@@ -1,6 +1,9 @@
+pub mod audit;
+pub mod clock;
 pub mod data_stores;
 pub mod email_client;
 pub mod error;
+pub mod totp;
 pub mod user;
 
 pub use email_client::*;
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::utils::redact::redacted_debug;
+
+// RFC 6238 defaults, which is what authenticator apps expect
+pub const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+// Codes from the previous and next time steps are accepted as well, to
+// tolerate clock drift and slow typing.
+const TOTP_SKEW_STEPS: u8 = 1;
+// 160 bits, as recommended by RFC 4226
+const TOTP_SECRET_BYTES: usize = 20;
+
+/// Shared secret of a TOTP authenticator, base32 encoded.
+#[derive(Clone, PartialEq)]
+pub struct TotpSecret(String);
+
+redacted_debug!(TotpSecret);
+
+impl TotpSecret {
+    pub fn parse(secret: String) -> Result<Self, String> {
+        let bytes = Secret::Encoded(secret.clone())
+            .to_bytes()
+            .map_err(|e| format!("Invalid TOTP secret. Details: {e:?}"))?;
+
+        if bytes.len() < 16 {
+            return Err("Invalid TOTP secret. Expected at least 128 bits.".into());
+        }
+
+        Ok(Self(secret))
+    }
+
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; TOTP_SECRET_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+
+        match Secret::Raw(bytes.to_vec()).to_encoded() {
+            Secret::Encoded(secret) => Self(secret),
+            Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+        }
+    }
+
+    /// `otpauth://` URI to be imported (usually as a QR code) in an
+    /// authenticator app.
+    pub fn otpauth_uri(&self, issuer: &str, account_name: &str) -> Result<String, String> {
+        self.totp(Some(issuer.to_owned()), account_name.to_owned())
+            .map(|totp| totp.get_url())
+    }
+
+    /// Checks the code against the time step of `now`, and its neighbours,
+    /// returning the step it belongs to. Steps up to `last_step` are skipped:
+    /// a code must not be accepted twice.
+    pub fn verify(&self, code: &str, now: DateTime<Utc>, last_step: Option<u64>) -> Option<u64> {
+        let totp = self.totp(None, String::new()).ok()?;
+        let current = timestamp(now)? / TOTP_STEP_SECONDS;
+        let skew = u64::from(TOTP_SKEW_STEPS);
+
+        (current.saturating_sub(skew)..=current + skew)
+            .filter(|&step| last_step.is_none_or(|last_step| step > last_step))
+            .find(|&step| totp.check(code, step * TOTP_STEP_SECONDS))
+    }
+
+    /// Code an authenticator app would display at `now`.
+    pub fn generate_code(&self, now: DateTime<Utc>) -> Result<String, String> {
+        let time = timestamp(now).ok_or("Invalid time for TOTP code")?;
+        self.totp(None, String::new())
+            .map(|totp| totp.generate(time))
+    }
+
+    fn totp(&self, issuer: Option<String>, account_name: String) -> Result<TOTP, String> {
+        let secret = Secret::Encoded(self.0.clone())
+            .to_bytes()
+            .map_err(|e| format!("Invalid TOTP secret. Details: {e:?}"))?;
+
+        // skew is handled by `verify`, one step at a time
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            secret,
+            issuer,
+            account_name,
+        )
+        .map_err(|e| format!("Unable to build TOTP. Details: {e:?}"))
+    }
+}
+
+impl AsRef<str> for TotpSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> Option<u64> {
+    time.timestamp().try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    // RFC 6238, appendix B: SHA1 test vectors use the ASCII secret
+    // "12345678901234567890" (base32 below) and 8 digit codes. We use 6
+    // digits, which are the last 6 digits of the reference values.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn should_match_rfc_6238_test_vectors() {
+        let secret = TotpSecret::parse(RFC_SECRET.into()).unwrap();
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+
+        for (time, expected) in vectors {
+            let now = Utc.timestamp_opt(time, 0).unwrap();
+            assert_eq!(secret.generate_code(now).unwrap(), expected);
+            assert!(secret.verify(expected, now, None).is_some());
+        }
+    }
+
+    #[test]
+    fn should_accept_codes_within_one_step_of_skew() {
+        let secret = TotpSecret::parse(RFC_SECRET.into()).unwrap();
+        let now = Utc.timestamp_opt(1_700_000_025, 0).unwrap();
+        let step = Duration::try_seconds(TOTP_STEP_SECONDS as i64).unwrap();
+
+        let previous = secret.generate_code(now - step).unwrap();
+        let next = secret.generate_code(now + step).unwrap();
+        let too_old = secret.generate_code(now - step * 2).unwrap();
+        let too_new = secret.generate_code(now + step * 2).unwrap();
+
+        assert!(secret
+            .verify(&secret.generate_code(now).unwrap(), now, None)
+            .is_some());
+        assert!(secret.verify(&previous, now, None).is_some());
+        assert!(secret.verify(&next, now, None).is_some());
+        assert!(secret.verify(&too_old, now, None).is_none());
+        assert!(secret.verify(&too_new, now, None).is_none());
+    }
+
+    #[test]
+    fn should_reject_codes_of_steps_already_used() {
+        let secret = TotpSecret::parse(RFC_SECRET.into()).unwrap();
+        let now = Utc.timestamp_opt(1_700_000_025, 0).unwrap();
+        let step = Duration::try_seconds(TOTP_STEP_SECONDS as i64).unwrap();
+        let code = secret.generate_code(now).unwrap();
+
+        let used_step = secret.verify(&code, now, None).unwrap();
+        assert_eq!(used_step, 1_700_000_025 / TOTP_STEP_SECONDS);
+        assert_eq!(secret.verify(&code, now, Some(used_step)), None);
+
+        // a code of the previous step, within skew but older than the last used
+        let previous = secret.generate_code(now - step).unwrap();
+        assert_eq!(secret.verify(&previous, now, Some(used_step)), None);
+
+        let next = secret.generate_code(now + step).unwrap();
+        assert_eq!(
+            secret.verify(&next, now, Some(used_step)),
+            Some(used_step + 1)
+        );
+    }
+
+    #[test]
+    fn should_generate_distinct_secrets_of_160_bits() {
+        let first = TotpSecret::generate();
+        let second = TotpSecret::generate();
+
+        assert_ne!(first, second);
+        assert_eq!(
+            Secret::Encoded(first.as_ref().to_owned())
+                .to_bytes()
+                .unwrap()
+                .len(),
+            TOTP_SECRET_BYTES
+        );
+        assert!(TotpSecret::parse(first.as_ref().to_owned()).is_ok());
+    }
+
+    #[test]
+    fn should_reject_invalid_secrets() {
+        assert!(TotpSecret::parse("not base32!".into()).is_err());
+        assert!(TotpSecret::parse("GEZDGNBV".into()).is_err());
+    }
+
+    #[test]
+    fn should_build_otpauth_uri() {
+        let secret = TotpSecret::parse(RFC_SECRET.into()).unwrap();
+        let uri = secret
+            .otpauth_uri("Auth Service", "user@example.com")
+            .unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/Auth%20Service:user%40example.com?"));
+        assert!(uri.contains(&format!("secret={RFC_SECRET}")));
+        assert!(uri.contains("issuer=Auth%20Service"));
+    }
+}
@@ -1,22 +1,82 @@
-use super::data_stores::user::UserStoreError;
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+};
+use chrono::{DateTime, Utc};
+
+use crate::utils::redact::redacted_debug;
+
+use super::{data_stores::user::UserStoreError, totp::TotpSecret};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct User {
     pub email: Email,
-    pub password: Password,
-    pub requires_2fa: bool,
+    pub password: HashedPassword,
+    pub two_fa_method: TwoFAMethod,
+    /// TOTP secret handed out at enrollment, only promoted to
+    /// `two_fa_method` once the user proved their app generates valid codes.
+    pub pending_totp_secret: Option<TotpSecret>,
+    /// Time step of the last TOTP code accepted, so that it can't be used
+    /// again.
+    pub last_totp_step: Option<u64>,
+    /// Whether the user proved they own `email`, by following the link of
+    /// the verification email.
+    pub verified: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    /// Creates an unverified user with no 2FA, or with emailed codes if
+    /// `requires_2fa`. TOTP can only be set up later on, through enrollment.
+    pub fn new(email: Email, password: HashedPassword, requires_2fa: bool) -> Self {
+        let two_fa_method = if requires_2fa {
+            TwoFAMethod::Email
+        } else {
+            TwoFAMethod::None
+        };
+
         Self {
             email,
             password,
-            requires_2fa,
+            two_fa_method,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
+        }
+    }
+
+    pub fn requires_2fa(&self) -> bool {
+        self.two_fa_method != TwoFAMethod::None
+    }
+
+    /// Checks a code of `secret`, and remembers its time step when valid:
+    /// neither it nor any earlier code is accepted afterwards.
+    pub fn accept_totp_code(
+        &mut self,
+        secret: &TotpSecret,
+        code: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match secret.verify(code, now, self.last_totp_step) {
+            Some(step) => {
+                self.last_totp_step = Some(step);
+                true
+            }
+            None => false,
         }
     }
 }
 
+/// Second factor a user must provide after their password.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TwoFAMethod {
+    #[default]
+    None,
+    /// A `TwoFACode` is sent by email on each login
+    Email,
+    /// A code is generated by an authenticator app (RFC 6238)
+    Totp(TotpSecret),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Email(String);
 
@@ -46,9 +106,11 @@ impl AsRef<str> for Email {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Password(String);
 
+redacted_debug!(Password);
+
 impl Password {
     pub fn parse<S: AsRef<str>>(value: S) -> Result<Self, UserStoreError> {
         let str: &str = value.as_ref();
@@ -68,9 +130,134 @@ impl AsRef<str> for Password {
     }
 }
 
+/// Argon2id hash of a `Password`, stored as a PHC string
+/// (ie: `$argon2id$v=19$m=...,t=...,p=...$<salt>$<hash>`).
+#[derive(Clone, PartialEq)]
+pub struct HashedPassword(String);
+
+redacted_debug!(HashedPassword);
+
+impl HashedPassword {
+    /// Hashes the password with a freshly generated salt. The work is done
+    /// on the blocking thread pool so it doesn't stall the async executor.
+    pub async fn parse(
+        password: Password,
+        params: PasswordHashingParams,
+    ) -> Result<Self, UserStoreError> {
+        tokio::task::spawn_blocking(move || compute_password_hash(password.as_ref(), params))
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?
+            .map(Self)
+    }
+
+    /// Wraps an already computed PHC string (eg: read back from a store).
+    pub fn parse_password_hash(hash: String) -> Result<Self, String> {
+        PasswordHash::new(&hash).map_err(|e| format!("Invalid password hash. Details: {e:?}"))?;
+
+        Ok(Self(hash))
+    }
+
+    /// Hash of a random password nobody knows, made with the given
+    /// parameters. Verifying a candidate against it takes as long as against
+    /// a real hash, but never succeeds: logging in as someone who doesn't
+    /// exist must not be faster than with a wrong password.
+    ///
+    /// Hashing takes as long as verifying: compute it once, ahead of logins.
+    pub async fn dummy(params: PasswordHashingParams) -> Result<Self, UserStoreError> {
+        let password = SaltString::generate(&mut OsRng);
+        Self::parse(Password::parse(password.as_str())?, params).await
+    }
+
+    /// Checks the candidate against this hash. The comparison of the derived
+    /// keys is done in constant time by the `password-hash` crate.
+    pub async fn verify_raw_password(&self, candidate: &Password) -> Result<(), UserStoreError> {
+        let hash = self.0.clone();
+        let candidate = candidate.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let expected = PasswordHash::new(&hash).map_err(|_| UserStoreError::UnexpectedError)?;
+
+            Argon2::default()
+                .verify_password(candidate.as_ref().as_bytes(), &expected)
+                .map_err(|_| UserStoreError::InvalidCredentials("Passwords do not match".into()))
+        })
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+    }
+
+    /// Whether this hash was produced with something other than Argon2id and
+    /// the given parameters, meaning it should be recomputed on next login.
+    pub fn needs_rehash(&self, params: PasswordHashingParams) -> bool {
+        let Ok(hash) = PasswordHash::new(&self.0) else {
+            return true;
+        };
+
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        match Params::try_from(&hash) {
+            Ok(current) => {
+                current.m_cost() != params.memory_cost
+                    || current.t_cost() != params.iterations
+                    || current.p_cost() != params.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+impl AsRef<str> for HashedPassword {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Argon2id cost parameters used when hashing new passwords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PasswordHashingParams {
+    /// Memory size, in KiB
+    pub memory_cost: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Degree of parallelism (lanes)
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashingParams {
+    fn default() -> Self {
+        Self {
+            memory_cost: 15000,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+fn compute_password_hash(
+    password: &str,
+    params: PasswordHashingParams,
+) -> Result<String, UserStoreError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let params = Params::new(
+        params.memory_cost,
+        params.iterations,
+        params.parallelism,
+        None,
+    )
+    .map_err(|_| UserStoreError::UnexpectedError)?;
+
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| UserStoreError::UnexpectedError)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Email, Password};
+    use super::{Email, HashedPassword, Password, PasswordHashingParams, TwoFAMethod, User};
 
     #[test]
     fn should_return_email_ok_when_properly_parsed() {
@@ -115,4 +302,107 @@ mod tests {
 
         assert!(results.iter().all(|r| r.is_err()))
     }
+
+    #[tokio::test]
+    async fn should_hash_password_with_argon2id() {
+        let password = Password::parse("password").unwrap();
+        let hashed = HashedPassword::parse(password.clone(), PasswordHashingParams::default())
+            .await
+            .unwrap();
+
+        assert!(hashed.as_ref().starts_with("$argon2id$"));
+        assert_ne!(hashed.as_ref(), password.as_ref());
+        assert!(HashedPassword::parse_password_hash(hashed.as_ref().to_owned()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_salt_each_hash() {
+        let password = Password::parse("password").unwrap();
+        let first = HashedPassword::parse(password.clone(), PasswordHashingParams::default())
+            .await
+            .unwrap();
+        let second = HashedPassword::parse(password, PasswordHashingParams::default())
+            .await
+            .unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn should_verify_raw_password_against_hash() {
+        let password = Password::parse("password").unwrap();
+        let hashed = HashedPassword::parse(password.clone(), PasswordHashingParams::default())
+            .await
+            .unwrap();
+
+        assert!(hashed.verify_raw_password(&password).await.is_ok());
+        assert!(hashed
+            .verify_raw_password(&Password::parse("other password").unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn should_make_a_dummy_hash_matching_no_password() {
+        let params = PasswordHashingParams::default();
+        let dummy = HashedPassword::dummy(params).await.unwrap();
+
+        assert!(!dummy.needs_rehash(params));
+        assert!(dummy
+            .verify_raw_password(&Password::parse("password").unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn should_need_rehash_when_params_change() {
+        let password = Password::parse("password").unwrap();
+        let params = PasswordHashingParams::default();
+        let hashed = HashedPassword::parse(password, params).await.unwrap();
+
+        assert!(!hashed.needs_rehash(params));
+        assert!(hashed.needs_rehash(PasswordHashingParams {
+            iterations: params.iterations + 1,
+            ..params
+        }));
+        assert!(hashed.needs_rehash(PasswordHashingParams {
+            memory_cost: params.memory_cost * 2,
+            ..params
+        }));
+    }
+
+    #[tokio::test]
+    async fn should_map_requires_2fa_to_email_method() {
+        let password = HashedPassword::parse(
+            Password::parse("password").unwrap(),
+            PasswordHashingParams::default(),
+        )
+        .await
+        .unwrap();
+
+        let with_2fa = User::new(Email::default(), password.clone(), true);
+        let without_2fa = User::new(Email::default(), password, false);
+
+        assert_eq!(with_2fa.two_fa_method, TwoFAMethod::Email);
+        assert!(with_2fa.requires_2fa());
+        assert_eq!(without_2fa.two_fa_method, TwoFAMethod::None);
+        assert!(!without_2fa.requires_2fa());
+    }
+
+    #[tokio::test]
+    async fn should_not_show_passwords_when_debugged() {
+        let password = Password::parse("password123").unwrap();
+        let hashed = HashedPassword::parse(password.clone(), PasswordHashingParams::default())
+            .await
+            .unwrap();
+        let user = User::new(Email::default(), hashed.clone(), false);
+
+        assert_eq!(format!("{password:?}"), "Password([REDACTED])");
+        assert!(!format!("{user:?}").contains(hashed.as_ref()));
+    }
+
+    #[test]
+    fn should_reject_invalid_password_hash() {
+        assert!(HashedPassword::parse_password_hash("password".into()).is_err());
+    }
 }
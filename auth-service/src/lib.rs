@@ -1,18 +1,32 @@
-use std::error::Error;
+use std::{error::Error, net::SocketAddr, sync::Arc};
 
 use app_state::AppState;
 use axum::{
-    http::StatusCode,
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    http::{header, HeaderName, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
 use domain::error::AuthAPIError;
 use reqwest::Method;
-use routes::{login, logout, signup, verify_2fa, verify_token};
+use routes::{
+    change_password, confirm_password_reset, confirm_totp, delete_account, enroll_totp, introspect,
+    jwks, list_sessions, login, logout, logout_all, rate_limit, refresh, regenerate_recovery_codes,
+    request_password_reset, resend_verification_email, revoke_session, rotate_signing_key, signup,
+    verify_2fa, verify_email, verify_token, RateLimiter,
+};
 use serde::{Deserialize, Serialize};
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+use utils::telemetry::{make_request_span, REQUEST_ID_HEADER};
 
 pub mod app_state;
 pub mod domain;
@@ -27,6 +41,11 @@ pub struct ErrorResponse {
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            AuthAPIError::TooManyRequests(seconds) => Some(*seconds),
+            _ => None,
+        };
+
         let (status, error): (StatusCode, String) = match self {
             AuthAPIError::InvalidCredentials(details) => (
                 StatusCode::BAD_REQUEST,
@@ -37,8 +56,25 @@ impl IntoResponse for AuthAPIError {
                 StatusCode::UNAUTHORIZED,
                 "Access to server limitted or no access granted.".into(),
             ),
+            AuthAPIError::TwoFACodeExpired => (
+                StatusCode::UNAUTHORIZED,
+                "2FA code expired, please log in again.".into(),
+            ),
+            AuthAPIError::TooManyTwoFAAttempts => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many incorrect 2FA codes, please log in again.".into(),
+            ),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token".into()),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token".into()),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found".into()),
+            AuthAPIError::EmailNotVerified => (
+                StatusCode::FORBIDDEN,
+                "Email address not verified, please check your inbox.".into(),
+            ),
+            AuthAPIError::TooManyRequests(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests, please try again later.".into(),
+            ),
             AuthAPIError::GenerateTokenError(e) => (
                 StatusCode::BAD_REQUEST,
                 format!("Generate token error: {e:?}"),
@@ -53,12 +89,21 @@ impl IntoResponse for AuthAPIError {
         };
 
         let body = Json(ErrorResponse { error });
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => {
+                (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response()
+            }
+            None => (status, body).into_response(),
+        }
     }
 }
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    // with the address of the client, to record where sessions come from
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     pub address: String,
 }
 
@@ -70,29 +115,62 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        let rate_limiter = Arc::new(RateLimiter::new(&state.settings));
+        let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
+            .route("/verify-email", post(verify_email))
+            .route("/verify-email/resend", post(resend_verification_email))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/2fa/totp/enroll", post(enroll_totp))
+            .route("/2fa/totp/confirm", post(confirm_totp))
+            .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
             .route("/logout", post(logout))
+            .route("/logout-all", post(logout_all))
+            .route("/refresh", post(refresh))
+            .route("/change-password", post(change_password))
+            .route("/account", delete(delete_account))
+            .route("/password-reset/request", post(request_password_reset))
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/sessions", get(list_sessions))
+            .route("/sessions/:id", delete(revoke_session))
             .route("/verify-token", post(verify_token))
+            .route("/introspect", post(introspect))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route("/admin/keys/rotate", post(rotate_signing_key))
+            // after routing, to know which route's limit applies
+            .route_layer(middleware::from_fn_with_state(rate_limiter, rate_limit))
             .with_state(state)
-            .layer(cors);
+            .layer(cors)
+            // outermost layers last: an ID is set on each request, unless it
+            // came with one (eg: from a proxy), before its span is opened
+            .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_request_span)
+                    .on_response(DefaultOnResponse::new().level(Level::INFO)),
+            )
+            .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid));
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Application { server, address })
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
-        println!("listening on {}", &self.address);
+        tracing::info!("listening on {}", &self.address);
         self.server.await
     }
 }
@@ -1,21 +1,117 @@
+use std::sync::Arc;
+
 use auth_service::{
-    app_state::AppState,
+    app_state::{AppState, AuditSinkType, RecoveryCodeStoreType, UserStoreType},
     services::{
         hashmap_banned_token_store::HashmapBannedTokenStore,
+        hashmap_failed_login_store::HashmapFailedLoginStore,
+        hashmap_one_time_token_store::HashmapOneTimeTokenStore,
+        hashmap_recovery_code_store::HashmapRecoveryCodeStore,
+        hashmap_refresh_token_store::HashmapRefreshTokenStore,
+        hashmap_session_store::HashmapSessionStore,
         hashmap_two_fa_code_store::HashmapTwoFACodeStore, hashmap_user_store::HashmapUserStore,
-        mock_email_client::MockEmailClient,
+        json_lines_audit_sink::JsonLinesAuditSink, mock_email_client::MockEmailClient,
+        sqlite_recovery_code_store::SqliteRecoveryCodeStore, sqlite_user_store::SqliteUserStore,
+    },
+    utils::{
+        constants::{env, prod},
+        keys::{JwtSigningKey, Keyring},
+        settings::{AppSettings, UserStoreBackend},
+        telemetry::init_tracing,
+        ThreadSafe,
     },
-    utils::{constants::prod, ThreadSafe},
     Application,
 };
+use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() {
+    let settings = AppSettings::from_env();
+    init_tracing(settings.log_format);
+
+    // recovery codes are kept wherever the users are
+    let (user_store, recovery_code_store): (UserStoreType, RecoveryCodeStoreType) =
+        match &settings.user_store {
+            UserStoreBackend::InMemory => (
+                HashmapUserStore::thread_safe(),
+                HashmapRecoveryCodeStore::thread_safe(),
+            ),
+            UserStoreBackend::Sqlite { url } => {
+                let store = SqliteUserStore::connect(url)
+                    .await
+                    .expect("Failed to open SQLite user store");
+                let recovery_code_store = SqliteRecoveryCodeStore::new(store.pool());
+                (
+                    Arc::new(RwLock::new(store)),
+                    Arc::new(RwLock::new(recovery_code_store)),
+                )
+            }
+        };
+
+    let audit_sink = match &settings.audit_log_path {
+        Some(path) => JsonLinesAuditSink::open(path)
+            .await
+            .expect("Failed to open audit log"),
+        None => JsonLinesAuditSink::default(),
+    };
+    let audit_sink: AuditSinkType = Arc::new(RwLock::new(audit_sink));
+
+    // the keys saved on the last rotation, if any, take over the configured one
+    let saved_keyring = settings
+        .jwt_keyring_path
+        .as_deref()
+        .map(Keyring::load)
+        .transpose()
+        .expect("Failed to load JWT keyring")
+        .flatten();
+
+    let keyring = match (saved_keyring, &settings.jwt_signing_key_path) {
+        (Some(keyring), _) => keyring,
+        (None, Some(path)) => Keyring::new(
+            JwtSigningKey::from_pem_file(path).expect("Failed to load JWT signing key"),
+        ),
+        (None, None) if settings.allow_ephemeral_signing_key => {
+            tracing::warn!("No JWT signing key configured, using a throwaway one: tokens won't survive a restart");
+            Keyring::new(JwtSigningKey::generate())
+        }
+        (None, None) => panic!(
+            "No JWT signing key configured: set {} (or {}=true for development)",
+            env::JWT_SIGNING_KEY_PATH_ENV_VAR,
+            env::ALLOW_EPHEMERAL_SIGNING_KEY_ENV_VAR
+        ),
+    };
+
+    if settings.log_email_content {
+        tracing::warn!("Emails aren't sent but logged at debug level, codes and tokens included");
+    } else {
+        tracing::warn!(
+            "Emails aren't sent: set {}=true to read them from the logs in development",
+            env::LOG_EMAIL_CONTENT_ENV_VAR
+        );
+    }
+    let email_client = Arc::new(RwLock::new(MockEmailClient::new(
+        settings.log_email_content,
+    )));
+
     let app_state = AppState::default()
-        .user_store(HashmapUserStore::thread_safe())
+        .user_store(user_store)
         .banned_token_store(HashmapBannedTokenStore::thread_safe())
         .two_fa_code_store(HashmapTwoFACodeStore::thread_safe())
-        .email_client(MockEmailClient::thread_safe());
+        .recovery_code_store(recovery_code_store)
+        .refresh_token_store(HashmapRefreshTokenStore::thread_safe())
+        .session_store(HashmapSessionStore::thread_safe())
+        .one_time_token_store(HashmapOneTimeTokenStore::thread_safe())
+        .failed_login_store(HashmapFailedLoginStore::thread_safe())
+        .audit_sink(audit_sink)
+        .email_client(email_client)
+        .keyring(Arc::new(RwLock::new(keyring)))
+        .settings(settings);
+
+    // ahead of the first login of an unknown user, who'd wait twice as long
+    app_state
+        .dummy_password_hash()
+        .await
+        .expect("Failed to hash the dummy password");
 
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
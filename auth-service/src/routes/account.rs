@@ -0,0 +1,148 @@
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::twofa::{LoginAttemptId, TwoFACodeStoreError},
+        error::AuthAPIError,
+        user::{Email, Password},
+    },
+    utils::{
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+        redact::redacted_debug,
+    },
+};
+
+use super::{
+    login::{confirm_password, start_two_fa_challenge, TwoFactorAuthResponse},
+    sessions::end_all_sessions,
+    utils::{
+        map_one_time_token_store_error_to_api_error, map_recovery_code_store_error_to_api_error,
+        map_string_error_to_bad_input_error, map_two_fa_code_store_error_to_api_error,
+        map_user_store_error_to_api_error, validate_token_from_cookie_jar, ClientInfo,
+    },
+    verify_2fa::{check_second_factor, parse_second_factor},
+};
+
+/// The password is always required. Users with 2FA first get a login attempt
+/// by sending it alone, then send it again along with the attempt's ID and
+/// either a `2FACode` or a `recoveryCode`.
+#[derive(Serialize, Clone, PartialEq, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Option<String>,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: Option<String>,
+}
+
+redacted_debug!(DeleteAccountRequest);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DeleteAccountResponse {
+    Deleted(AccountDeletedResponse),
+    TwoFactorAuth(TwoFactorAuthResponse),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountDeletedResponse {
+    pub message: String,
+}
+
+/// Closes the account of the logged in user, who must authenticate again.
+/// Everything stored about them is removed, and all their tokens revoked.
+pub async fn delete_account(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<(CookieJar, (StatusCode, Json<DeleteAccountResponse>)), AuthAPIError> {
+    let (_, email, _) = validate_token_from_cookie_jar(jar.clone(), &client, &state).await?;
+
+    let password = Password::parse(request.password).map_err(map_user_store_error_to_api_error)?;
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(email.to_owned())
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    confirm_password(&user, &password, &client, &state).await?;
+
+    if user.requires_2fa() {
+        let Some(login_attempt_id) = request.login_attempt_id else {
+            let response = start_two_fa_challenge(&user, &client, &state).await?;
+            return Ok((
+                jar,
+                (
+                    StatusCode::PARTIAL_CONTENT,
+                    Json(DeleteAccountResponse::TwoFactorAuth(response)),
+                ),
+            ));
+        };
+
+        let login_attempt_id =
+            LoginAttemptId::parse(login_attempt_id).map_err(map_string_error_to_bad_input_error)?;
+        let second_factor =
+            parse_second_factor(&email, request.two_fa_code, request.recovery_code, &state).await?;
+        check_second_factor(&email, &login_attempt_id, second_factor, &client, &state).await?;
+    }
+
+    purge_account(&email, &state).await?;
+
+    let jar = jar
+        .remove(Cookie::from(JWT_COOKIE_NAME))
+        .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME));
+    let response = DeleteAccountResponse::Deleted(AccountDeletedResponse {
+        message: "Account deleted successfully.".into(),
+    });
+
+    Ok((jar, (StatusCode::OK, Json(response))))
+}
+
+// Only the bans of the user's tokens are kept: they must stay rejected
+// until they expire.
+async fn purge_account(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    end_all_sessions(email, state).await?;
+
+    // a pending login attempt, if any
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(email)
+        .await
+    {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+        Err(e) => return Err(map_two_fa_code_store_error_to_api_error(e)),
+    }
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .remove_codes(email)
+        .await
+        .map_err(map_recovery_code_store_error_to_api_error)?;
+
+    state
+        .one_time_token_store
+        .write()
+        .await
+        .remove_tokens(email)
+        .await
+        .map_err(map_one_time_token_store_error_to_api_error)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .delete_user(email)
+        .await
+        .map_err(map_user_store_error_to_api_error)
+}
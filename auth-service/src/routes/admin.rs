@@ -0,0 +1,74 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::error::AuthAPIError,
+    utils::{auth::TOKEN_TTL_SECONDS, keys::JwtSigningKey},
+};
+
+use super::utils::authorize_api_key;
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct RotateSigningKeyResponse {
+    pub kid: String,
+    #[serde(rename = "previousKid")]
+    pub previous_kid: String,
+    /// RFC 3339 timestamp after which tokens signed with the previous key
+    /// are no longer accepted
+    #[serde(rename = "previousKeyRetiresAt")]
+    pub previous_key_retires_at: String,
+}
+
+/// Starts signing JWTs with a new key. The previous key keeps verifying the
+/// tokens it signed until they have all expired, so no session is lost.
+///
+/// The keys are saved to the keyring file before being used, so that a
+/// restart doesn't go back to the configured key. Without a keyring file,
+/// rotations only make sense with a throwaway key, lost on restart anyway.
+pub async fn rotate_signing_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_api_key(&headers, state.settings.admin_api_key.as_deref())?;
+
+    let settings = &state.settings;
+    if settings.jwt_keyring_path.is_none() && settings.jwt_signing_key_path.is_some() {
+        return Err(AuthAPIError::BadInput(
+            "Rotating a configured signing key needs a keyring file to save the new one to".into(),
+        ));
+    }
+
+    let key = JwtSigningKey::generate();
+    let kid = key.kid().to_owned();
+    let previous_key_retires_at = Utc::now() + chrono::Duration::seconds(TOKEN_TTL_SECONDS);
+
+    let mut keyring = state.keyring.write().await;
+    let previous_kid = keyring.active().kid().to_owned();
+    let mut rotated = keyring.clone();
+    rotated.rotate(key, previous_key_retires_at);
+
+    if let Some(path) = &settings.jwt_keyring_path {
+        rotated.save(path).await.map_err(|e| {
+            tracing::error!(error = %e, "Unable to save rotated signing key");
+            AuthAPIError::UnexpectedError
+        })?;
+    }
+    *keyring = rotated;
+
+    Ok((
+        StatusCode::OK,
+        Json(RotateSigningKeyResponse {
+            kid,
+            previous_kid,
+            previous_key_retires_at: previous_key_retires_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }),
+    ))
+}
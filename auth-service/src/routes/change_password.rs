@@ -0,0 +1,109 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::session::SessionId,
+        error::AuthAPIError,
+        user::{Email, HashedPassword, Password},
+    },
+    utils::redact::redacted_debug,
+};
+
+use super::{
+    login::confirm_password,
+    sessions::end_other_sessions,
+    utils::{
+        map_string_error_to_api_error, map_user_store_error_to_api_error,
+        validate_token_from_cookie_jar, ClientInfo,
+    },
+};
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+redacted_debug!(ChangePasswordRequest);
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
+
+/// Replaces the password of the logged in user, who must confirm their
+/// current one. Their other sessions are ended, this one is kept.
+pub async fn change_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, email, claims) = validate_token_from_cookie_jar(jar, &client, &state).await?;
+
+    let current_password =
+        Password::parse(request.current_password).map_err(map_user_store_error_to_api_error)?;
+    let new_password =
+        Password::parse(request.new_password).map_err(map_user_store_error_to_api_error)?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(email.to_owned())
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    confirm_password(&user, &current_password, &client, &state).await?;
+
+    if new_password == current_password {
+        return Err(AuthAPIError::BadInput(
+            "The new password must differ from the current one".into(),
+        ));
+    }
+
+    let new_password = HashedPassword::parse(new_password, state.settings.password_hashing)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(email.to_owned(), new_password)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    let current_session_id = SessionId::parse(claims.sid).map_err(map_string_error_to_api_error)?;
+    end_other_sessions(&email, &current_session_id, &state).await?;
+
+    // the password was changed regardless
+    if let Err(e) = send_password_changed_email(&email, &state).await {
+        tracing::error!(error = ?e, "Unable to send password change notification");
+    }
+
+    let response = Json(ChangePasswordResponse {
+        message: "Password changed successfully, other sessions were logged out.".into(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+async fn send_password_changed_email(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let email_client = state.email_client.read().await;
+
+    email_client
+        .send_email(
+            email,
+            "Your password was changed",
+            "The password of your account was just changed, and your other devices were logged out.\nIf you did not do this, reset your password right away and review your active sessions.",
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(error = ?e, "Unable to send email");
+            AuthAPIError::UnexpectedError
+        })
+}
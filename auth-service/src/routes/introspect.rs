@@ -0,0 +1,91 @@
+use axum::{extract::State, http::HeaderMap, Form, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::error::AuthAPIError,
+    utils::{
+        auth::{validate_token, Claims, TokenValidationError},
+        redact::redacted_debug,
+    },
+};
+
+use super::utils::{authorize_api_key, map_token_validation_error_to_api_error};
+
+#[derive(Serialize, Deserialize)]
+pub struct IntrospectRequest {
+    pub token: String,
+    // accepted for compatibility with RFC 7662 clients, we only issue JWTs
+    pub token_type_hint: Option<String>,
+}
+
+redacted_debug!(IntrospectRequest);
+
+/// RFC 7662 introspection response. Only `active` is set for tokens which
+/// aren't (invalid, expired, revoked...), so as not to tell why. There's no
+/// `scope`: our tokens aren't scoped, they all grant the same access.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(rename = "2fa", skip_serializing_if = "Option::is_none")]
+    pub two_fa: Option<bool>,
+}
+
+impl From<Claims> for IntrospectResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            jti: Some(claims.jti),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            token_type: Some("Bearer".to_owned()),
+            two_fa: Some(claims.two_fa),
+        }
+    }
+}
+
+/// Tells a trusted service whether a JWT is active and what it says about
+/// its user. Callers authenticate with the introspection API key.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<Json<IntrospectResponse>, AuthAPIError> {
+    authorize_api_key(&headers, state.settings.introspection_api_key.as_deref())?;
+
+    let result = validate_token(
+        &request.token,
+        state.keyring.clone(),
+        &state.settings.jwt,
+        state.banned_token_store.clone(),
+    )
+    .await;
+
+    match result {
+        Ok(claims) => Ok(Json(claims.into())),
+        Err(e @ TokenValidationError::BannedTokenStoreError(_)) => {
+            Err(map_token_validation_error_to_api_error(e))
+        }
+        Err(_) => Ok(Json(IntrospectResponse::default())),
+    }
+}
@@ -0,0 +1,18 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::app_state::AppState;
+
+/// Public keys verifying the JWTs we issue (RFC 7517), looked up by the `kid`
+/// header of the tokens.
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(state.keyring.read().await.jwks()),
+    )
+}
@@ -5,21 +5,34 @@ use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
     domain::{
-        data_stores::twofa::{LoginAttemptId, TwoFACode},
+        audit::{AuditEventKind, LoginFailure},
+        data_stores::{
+            failed_login::FailedLoginKey,
+            twofa::{LoginAttemptId, TwoFACode},
+            user::{UserStoreError, UserStoreResult},
+        },
         error::AuthAPIError,
-        user::{Email, Password},
+        user::{Email, HashedPassword, Password, TwoFAMethod, User},
     },
-    utils::auth::generate_auth_cookie,
+    utils::redact::redacted_debug,
 };
 
-use super::utils::map_user_store_error_to_api_error;
+use super::{
+    sessions::start_session,
+    utils::{
+        audit, client_network, map_failed_login_store_error_to_api_error,
+        map_user_store_error_to_api_error, throttle_until, ClientInfo,
+    },
+};
 
-#[derive(Serialize, PartialEq, Debug, Deserialize)]
+#[derive(Serialize, PartialEq, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
 }
 
+redacted_debug!(LoginRequest);
+
 impl LoginRequest {
     pub fn parse_email(&self) -> Result<Email, AuthAPIError> {
         Email::parse(self.email.clone()).map_err(map_user_store_error_to_api_error)
@@ -42,53 +55,320 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    /// Where the code is expected to come from: `"email"` or `"totp"`
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
 }
 
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(login_request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
-    let user_store = state.user_store.write().await;
-
     let email = login_request.parse_email()?;
     let password = login_request.parse_password()?;
 
-    user_store
-        .validate_user(email.to_owned(), password)
-        .map_err(map_user_store_error_to_api_error)?;
+    let failed_login_keys = failed_login_keys(&email, &client);
+    if let Err(e) = refuse_if_locked(&failed_login_keys, &state).await {
+        audit_login_failure(LoginFailure::Locked, &email, &client, &state).await;
+        return Err(e);
+    }
+
+    let user = match validate_credentials(&email, &password, &state).await {
+        Ok(user) => user,
+        // the same answer either way, not to tell which accounts exist
+        Err(e @ (UserStoreError::InvalidCredentials(_) | UserStoreError::UserNotFound)) => {
+            let reason = match e {
+                UserStoreError::UserNotFound => LoginFailure::UnknownUser,
+                _ => LoginFailure::WrongPassword,
+            };
+            audit_login_failure(reason, &email, &client, &state).await;
+            record_failed_login(&failed_login_keys, &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(map_user_store_error_to_api_error(e)),
+    };
+    reset_failed_logins(&email, &state).await?;
+
+    if state.settings.require_verified_email && !user.verified {
+        audit_login_failure(LoginFailure::EmailNotVerified, &email, &client, &state).await;
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    rehash_password_if_needed(&user, password, &state).await;
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_regular(&user.email, client, &state, jar).await,
+        TwoFAMethod::Email | TwoFAMethod::Totp(_) => handle_2fa(&user, &client, &state, jar).await,
+    }
+}
+
+async fn audit_login_failure(
+    reason: LoginFailure,
+    email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
+) {
+    let kind = AuditEventKind::LoginFailed { reason };
+    audit(state, kind, Some(email), client).await;
+}
 
-    let user = user_store
+// Unknown users get their password checked against a dummy hash, so that
+// they take as long as users mistyping theirs.
+async fn validate_credentials(
+    email: &Email,
+    password: &Password,
+    state: &AppState,
+) -> UserStoreResult<User> {
+    let user = state
+        .user_store
+        .read()
+        .await
         .get_user(email.to_owned())
-        .map_err(map_user_store_error_to_api_error)?;
+        .await;
+
+    match user {
+        Ok(user) => {
+            user.password.verify_raw_password(password).await?;
+            Ok(user)
+        }
+        Err(UserStoreError::UserNotFound) => {
+            let dummy = state.dummy_password_hash().await?;
+            let _ = dummy.verify_raw_password(password).await;
+            Err(UserStoreError::UserNotFound)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Wrong passwords are counted against the account, whether it exists or not,
+// and against the address of the client (or its IPv6 network), if known.
+pub(super) fn failed_login_keys(email: &Email, client: &ClientInfo) -> Vec<FailedLoginKey> {
+    let mut keys = vec![FailedLoginKey::Account(email.to_owned())];
+    if let Some(ip) = &client.ip {
+        let network = match ip.parse() {
+            Ok(ip) => client_network(ip).to_string(),
+            Err(_) => ip.to_owned(),
+        };
+        keys.push(FailedLoginKey::Ip(network));
+    }
+
+    keys
+}
+
+pub(super) async fn refuse_if_locked(
+    keys: &[FailedLoginKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let failed_login_store = state.failed_login_store.read().await;
+
+    for key in keys {
+        let locked_until = failed_login_store
+            .locked_until(key)
+            .await
+            .map_err(map_failed_login_store_error_to_api_error)?;
+        if let Some(locked_until) = locked_until {
+            throttle_until(locked_until)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) async fn record_failed_login(
+    keys: &[FailedLoginKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let mut failed_login_store = state.failed_login_store.write().await;
+
+    for key in keys {
+        failed_login_store
+            .record_failure(key)
+            .await
+            .map_err(map_failed_login_store_error_to_api_error)?;
+    }
+
+    Ok(())
+}
+
+// Only the account is cleared: one right password must not wipe the
+// failures of an address guessing many others.
+pub(super) async fn reset_failed_logins(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    state
+        .failed_login_store
+        .write()
+        .await
+        .reset(&FailedLoginKey::Account(email.to_owned()))
+        .await
+        .map_err(map_failed_login_store_error_to_api_error)
+}
+
+/// Checks that a logged in user making a sensitive change is who they claim:
+/// with their password, or a code of their active TOTP secret, which then
+/// can't be used again.
+pub(super) async fn reauthenticate(
+    email: &Email,
+    password: Option<String>,
+    code: Option<String>,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match (password, code) {
+        (Some(password), _) => {
+            let password = Password::parse(password).map_err(map_user_store_error_to_api_error)?;
+            // read apart from the update: the password hash takes a while to check
+            let user = state
+                .user_store
+                .read()
+                .await
+                .get_user(email.to_owned())
+                .await
+                .map_err(map_user_store_error_to_api_error)?;
+            confirm_password(&user, &password, client, state).await
+        }
+        (None, Some(code)) => confirm_totp_code(email, &code, client, state).await,
+        (None, None) => Err(AuthAPIError::BadInput(
+            "The password or a current TOTP code is required".into(),
+        )),
+    }
+}
+
+// Wrong codes count towards the same lockouts as wrong passwords, or a
+// stolen session could guess them.
+async fn confirm_totp_code(
+    email: &Email,
+    code: &str,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let failed_login_keys = failed_login_keys(email, client);
+    refuse_if_locked(&failed_login_keys, state).await?;
+
+    let now = state.clock.read().await.now();
+    let accepted = {
+        let mut user_store = state.user_store.write().await;
+        let mut user = user_store
+            .get_user(email.to_owned())
+            .await
+            .map_err(map_user_store_error_to_api_error)?;
+        let TwoFAMethod::Totp(secret) = user.two_fa_method.clone() else {
+            return Err(AuthAPIError::BadInput("TOTP is not enabled".into()));
+        };
+
+        let accepted = user.accept_totp_code(&secret, code, now);
+        if accepted {
+            user_store
+                .update_user(user)
+                .await
+                .map_err(map_user_store_error_to_api_error)?;
+        }
+        accepted
+    };
 
-    if user.requires_2fa {
-        handle_2fa(&user.email, &state, jar).await
+    if accepted {
+        reset_failed_logins(email, state).await
     } else {
-        handle_regular(&user.email, jar).await
+        record_failed_login(&failed_login_keys, state).await?;
+        Err(AuthAPIError::IncorrectCredentials)
+    }
+}
+
+/// Checks the password a logged in user confirms a sensitive change with.
+/// Wrong ones count towards the same lockouts as failed logins, so that a
+/// stolen session can't be used to guess it.
+pub(super) async fn confirm_password(
+    user: &User,
+    password: &Password,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let failed_login_keys = failed_login_keys(&user.email, client);
+    refuse_if_locked(&failed_login_keys, state).await?;
+
+    match user.password.verify_raw_password(password).await {
+        Ok(()) => reset_failed_logins(&user.email, state).await,
+        // the user is logged in: a wrong password is not a malformed request
+        Err(UserStoreError::InvalidCredentials(_)) => {
+            record_failed_login(&failed_login_keys, state).await?;
+            Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => Err(map_user_store_error_to_api_error(e)),
+    }
+}
+
+// Upgrade hashes produced with older parameters while we still hold the
+// plaintext password. Failing to do so must not prevent the login.
+async fn rehash_password_if_needed(user: &User, password: Password, state: &AppState) {
+    let params = state.settings.password_hashing;
+    if !user.password.needs_rehash(params) {
+        return;
+    }
+
+    let result = match HashedPassword::parse(password, params).await {
+        Ok(hashed_password) => {
+            state
+                .user_store
+                .write()
+                .await
+                .update_password(user.email.to_owned(), hashed_password)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        tracing::error!(error = ?e, "Unable to rehash password");
     }
 }
 
 async fn handle_regular(
     email: &Email,
+    client: ClientInfo,
+    state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let cookie = generate_auth_cookie(email).map_err(AuthAPIError::GenerateTokenError)?;
-    let jar = jar.add(cookie);
+    audit(state, AuditEventKind::LoginSucceeded, Some(email), &client).await;
+    let jar = start_session(email, false, client, state, jar).await?;
 
     Ok((jar, (StatusCode::OK, Json(LoginResponse::RegularAuth))))
 }
 
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
+    client: &ClientInfo,
     state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
+    let response = start_two_fa_challenge(user, client, state).await?;
+
+    Ok((
+        jar,
+        (
+            StatusCode::PARTIAL_CONTENT,
+            Json(LoginResponse::TwoFactorAuth(response)),
+        ),
+    ))
+}
+
+/// Starts a login attempt the user must complete with their second factor,
+/// emailing them a code unless they use an authenticator app.
+pub(super) async fn start_two_fa_challenge(
+    user: &User,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<TwoFactorAuthResponse, AuthAPIError> {
+    let email = &user.email;
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let email_client = state.email_client.read().await;
 
+    // TOTP users still get a login attempt, so that its expiry and failed
+    // attempts are tracked the same way. The code itself is never sent and
+    // never checked: it's the authenticator app's code that is expected.
     let login_attempt_id = LoginAttemptId::default();
-    let two_fa_code = TwoFACode::default();
+    let two_fa_code = TwoFACode::generate(state.settings.two_fa_code_length);
 
     two_fa_code_store
         .add_code(
@@ -98,29 +378,39 @@ async fn handle_2fa(
         )
         .await
         .map_err(|e| {
-            println!("[ERROR] Unexpected error while trying to store 2FA code: {e:?}");
+            tracing::error!(error = ?e, "Unable to store 2FA code");
             AuthAPIError::UnexpectedError
         })?;
 
+    let two_fa_method = match user.two_fa_method {
+        TwoFAMethod::Totp(_) => "totp",
+        _ => {
+            send_two_fa_code(email, &two_fa_code, state).await?;
+            audit(state, AuditEventKind::TwoFACodeSent, Some(email), client).await;
+            "email"
+        }
+    };
+
+    Ok(TwoFactorAuthResponse {
+        login_attempt_id: login_attempt_id.as_ref().into(),
+        message: "2FA required".into(),
+        two_fa_method: two_fa_method.into(),
+    })
+}
+
+async fn send_two_fa_code(
+    email: &Email,
+    two_fa_code: &TwoFACode,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let email_client = state.email_client.read().await;
+
     email_client.send_email(
         email,
         "[2FA] Login request to the best Auth system :p",
-        &format!("For security reason, you identity must be verified by entering the following code: {:?}", two_fa_code)
+        &format!("For security reason, you identity must be verified by entering the following code: {}", two_fa_code.as_ref())
     ).await.map_err(|e| {
-        println!("Unable to send email. Details: {e:?}");
+        tracing::error!(error = ?e, "Unable to send email");
         AuthAPIError::UnexpectedError
-    })?;
-
-    let response = TwoFactorAuthResponse {
-        login_attempt_id: login_attempt_id.as_ref().into(),
-        message: "2FA required".into(),
-    };
-
-    Ok((
-        jar,
-        (
-            StatusCode::PARTIAL_CONTENT,
-            Json(LoginResponse::TwoFactorAuth(response)),
-        ),
-    ))
+    })
 }
@@ -2,41 +2,98 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::{cookie::Cookie, CookieJar};
 
 use crate::{
-    app_state::{AppState, BannedTokenStoreType},
-    domain::{error::AuthAPIError, user::Email},
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    app_state::AppState,
+    domain::{
+        audit::AuditEventKind,
+        data_stores::{
+            refresh::RefreshTokenStoreError,
+            session::{SessionId, SessionStoreError},
+        },
+        error::AuthAPIError,
+    },
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+
+use super::{
+    refresh::parse_refresh_token_from_cookie_jar,
+    sessions::end_all_sessions,
+    utils::{audit, validate_token_from_cookie_jar, ClientInfo},
 };
 
 pub async fn logout(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let banned_token_store = state.banned_token_store.clone();
-
-    match validate_token_from_cookie_jar(jar.clone(), banned_token_store).await {
+    match validate_token_from_cookie_jar(jar.clone(), &client, &state).await {
         Err(error) => (jar, Err(error)),
-        Ok((cookie, token, email)) => {
-            let jar = jar.remove(cookie);
+        Ok((cookie, email, claims)) => {
             let mut banned_token_store = state.banned_token_store.write().await;
-            banned_token_store.add(&email, &token);
+            if let Err(e) = banned_token_store
+                .add(&email, &claims.jti, claims.exp)
+                .await
+            {
+                tracing::error!(error = ?e, "Unable to ban token on logout");
+                return (jar, Err(AuthAPIError::UnexpectedError));
+            }
+
+            // end the session as well, so the JWT can't be refreshed
+            if let Ok(refresh_token) = parse_refresh_token_from_cookie_jar(&jar) {
+                let mut refresh_token_store = state.refresh_token_store.write().await;
+                match refresh_token_store.revoke(&refresh_token).await {
+                    Ok(()) | Err(RefreshTokenStoreError::TokenNotFound) => {}
+                    Err(e) => {
+                        tracing::error!(error = ?e, "Unable to revoke refresh token on logout");
+                        return (jar, Err(AuthAPIError::UnexpectedError));
+                    }
+                }
+            }
+
+            if let Ok(session_id) = SessionId::parse(claims.sid) {
+                let mut session_store = state.session_store.write().await;
+                match session_store.remove_session(&email, &session_id).await {
+                    Ok(_) | Err(SessionStoreError::SessionNotFound) => {}
+                    Err(e) => {
+                        tracing::error!(error = ?e, "Unable to remove session on logout");
+                        return (jar, Err(AuthAPIError::UnexpectedError));
+                    }
+                }
+            }
 
+            let kind = AuditEventKind::Logout {
+                all_sessions: false,
+            };
+            audit(&state, kind, Some(&email), &client).await;
+
+            let jar = jar
+                .remove(cookie)
+                .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME));
             (jar, Ok(StatusCode::OK.into_response()))
         }
     }
 }
 
-async fn validate_token_from_cookie_jar(
+/// Logs the user out of every device: all their JWTs issued so far are
+/// banned, and all their refresh tokens revoked.
+pub async fn logout_all(
+    State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
-    banned_token_store: BannedTokenStoreType,
-) -> Result<(Cookie<'static>, String, Email), AuthAPIError> {
-    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
-    let token = cookie.value().to_owned();
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match validate_token_from_cookie_jar(jar.clone(), &client, &state).await {
+        Ok((_, email, _)) => email,
+        Err(error) => return (jar, Err(error)),
+    };
 
-    let claims = validate_token(&token, banned_token_store)
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if let Err(error) = end_all_sessions(&email, &state).await {
+        return (jar, Err(error));
+    }
 
-    let email = Email::parse(claims.sub).expect("Could not parse email address from store.");
+    let kind = AuditEventKind::Logout { all_sessions: true };
+    audit(&state, kind, Some(&email), &client).await;
 
-    Ok((cookie.to_owned(), token, email))
+    let jar = jar
+        .remove(Cookie::from(JWT_COOKIE_NAME))
+        .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME));
+    (jar, Ok(StatusCode::OK))
 }
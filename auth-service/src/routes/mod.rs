@@ -1,12 +1,36 @@
+mod account;
+mod admin;
+mod change_password;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
+mod sessions;
 mod signup;
+mod totp;
 pub mod utils;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use account::*;
+pub use admin::*;
+pub use change_password::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use password_reset::*;
+pub use rate_limit::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
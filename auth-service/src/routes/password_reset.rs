@@ -0,0 +1,165 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{
+            one_time::{OneTimeToken, OneTimeTokenPurpose, PASSWORD_RESET_TOKEN_TTL_SECONDS},
+            user::UserStoreError,
+        },
+        error::AuthAPIError,
+        user::{Email, HashedPassword, Password},
+    },
+    utils::{
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+        redact::redacted_debug,
+    },
+};
+
+use super::{
+    sessions::end_all_sessions,
+    utils::{
+        map_one_time_token_store_error_to_api_error, map_string_error_to_bad_input_error,
+        map_user_store_error_to_api_error,
+    },
+};
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+redacted_debug!(PasswordResetConfirmRequest);
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
+
+/// Emails a password reset token to the user. The response is the same, and
+/// takes as long, whether or not an account exists for the email, so that
+/// this can't be used to find out who has one.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(map_user_store_error_to_api_error)?;
+
+    let user = state.user_store.read().await.get_user(email).await;
+    match user {
+        Ok(user) => {
+            // sent off the request path, which would otherwise take longer
+            // for existing accounts. Failures aren't told to the caller
+            // either, for the same reason.
+            let state = state.clone();
+            let send = async move {
+                if let Err(e) = send_password_reset_token(&user.email, &state).await {
+                    tracing::error!(error = ?e, "Unable to send password reset token");
+                }
+            };
+            tokio::spawn(send.in_current_span());
+        }
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(map_user_store_error_to_api_error(e)),
+    }
+
+    let response = Json(PasswordResetResponse {
+        message: "If an account exists for this email, a password reset token was sent to it."
+            .into(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Sets a new password for the user the token was emailed to, and ends all
+/// their sessions: whoever knew the previous password is logged out.
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    match reset_password(request, &state).await {
+        Ok(()) => {
+            let jar = jar
+                .remove(Cookie::from(JWT_COOKIE_NAME))
+                .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME));
+            let response = Json(PasswordResetResponse {
+                message: "Password reset successfully, please log in again.".into(),
+            });
+
+            (jar, Ok((StatusCode::OK, response)))
+        }
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+async fn reset_password(
+    request: PasswordResetConfirmRequest,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = OneTimeToken::parse(request.token).map_err(map_string_error_to_bad_input_error)?;
+    let password =
+        Password::parse(request.new_password).map_err(map_user_store_error_to_api_error)?;
+
+    let email = state
+        .one_time_token_store
+        .write()
+        .await
+        .consume_token(OneTimeTokenPurpose::PasswordReset, &token)
+        .await
+        .map_err(map_one_time_token_store_error_to_api_error)?;
+
+    let password = HashedPassword::parse(password, state.settings.password_hashing)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(email.to_owned(), password)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    end_all_sessions(&email, state).await
+}
+
+async fn send_password_reset_token(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let purpose = OneTimeTokenPurpose::PasswordReset;
+    let token = OneTimeToken::generate();
+
+    state
+        .one_time_token_store
+        .write()
+        .await
+        .add_token(email, purpose, &token, Utc::now() + purpose.ttl())
+        .await
+        .map_err(map_one_time_token_store_error_to_api_error)?;
+
+    let email_client = state.email_client.read().await;
+    email_client
+        .send_email(
+            email,
+            "Password reset request",
+            &format!(
+                "Someone asked to reset the password of your account. If it was you, use the following token within {} minutes: {}\nOtherwise, you can safely ignore this email.",
+                PASSWORD_RESET_TOKEN_TTL_SECONDS / 60,
+                token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(error = ?e, "Unable to send email");
+            AuthAPIError::UnexpectedError
+        })
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    domain::error::AuthAPIError,
+    utils::settings::{AppSettings, RateLimit},
+};
+
+use super::utils::{client_ip, client_network};
+
+// Past that many buckets, the full ones are dropped: they'd behave the same
+// as new ones. If none is full, the least recently used one goes.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Outcome of a request against its bucket, as reported to the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_seconds: u64,
+    /// Seconds until the next request is allowed
+    pub retry_after_seconds: u64,
+}
+
+/// Token buckets per route and client address (or IPv6 network): each holds
+/// up to the route's number of requests, and refills at that many per period.
+pub struct RateLimiter {
+    limits: HashMap<String, RateLimit>,
+    trusted_proxy_hops: usize,
+    buckets: Mutex<HashMap<(String, IpAddr), TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: &AppSettings) -> Self {
+        Self {
+            limits: settings.rate_limits.clone(),
+            trusted_proxy_hops: settings.trusted_proxy_hops,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of the client for the route, if it's
+    /// limited at all.
+    pub fn check(&self, route: &str, ip: IpAddr, now: Instant) -> Option<RateLimitStatus> {
+        let limit = *self.limits.get(route)?;
+        let capacity = limit.requests as f64;
+
+        let key = (route.to_owned(), client_network(ip));
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_BUCKETS && !buckets.contains_key(&key) {
+            self.evict(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(key).or_insert(TokenBucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        bucket.tokens = refill(bucket, &limit, now);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let seconds_until =
+            |tokens: f64| (tokens.max(0.0) * limit.period_seconds as f64 / capacity).ceil() as u64;
+        Some(RateLimitStatus {
+            allowed,
+            limit: limit.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset_seconds: seconds_until(capacity - bucket.tokens),
+            retry_after_seconds: seconds_until(1.0 - bucket.tokens),
+        })
+    }
+
+    // Makes room for a new bucket
+    fn evict(&self, buckets: &mut HashMap<(String, IpAddr), TokenBucket>, now: Instant) {
+        buckets.retain(|(route, _), bucket| {
+            self.limits
+                .get(route)
+                .is_some_and(|limit| refill(bucket, limit, now) < limit.requests as f64)
+        });
+
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            let oldest = buckets
+                .iter()
+                .min_by_key(|(_, bucket)| bucket.updated_at)
+                .map(|(key, _)| key.to_owned());
+            if let Some(oldest) = oldest {
+                buckets.remove(&oldest);
+            }
+        }
+    }
+}
+
+// Tokens in the bucket by `now`
+fn refill(bucket: &TokenBucket, limit: &RateLimit, now: Instant) -> f64 {
+    let capacity = limit.requests as f64;
+    let elapsed = now
+        .saturating_duration_since(bucket.updated_at)
+        .as_secs_f64();
+
+    (bucket.tokens + elapsed * capacity / limit.period_seconds as f64).min(capacity)
+}
+
+/// Refuses requests with `429 Too Many Requests` once the client used up the
+/// limit of the route, and tells it where it stands with the `RateLimit-*`
+/// headers. Requests whose address is unknown aren't limited.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    let ip = client_ip(request.headers(), peer, limiter.trusted_proxy_hops);
+
+    let status = match (matched_path, ip) {
+        (Some(route), Some(ip)) => limiter.check(route.as_str(), ip, Instant::now()),
+        _ => None,
+    };
+    let Some(status) = status else {
+        return next.run(request).await;
+    };
+
+    let mut response = if status.allowed {
+        next.run(request).await
+    } else {
+        AuthAPIError::TooManyRequests(status.retry_after_seconds).into_response()
+    };
+    insert_rate_limit_headers(response.headers_mut(), &status);
+
+    response
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    for (name, value) in [
+        (RATE_LIMIT_LIMIT, status.limit as u64),
+        (RATE_LIMIT_REMAINING, status.remaining as u64),
+        (RATE_LIMIT_RESET, status.reset_seconds),
+    ] {
+        headers.insert(name, HeaderValue::from(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const ROUTE: &str = "/login";
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    fn limiter(limit: RateLimit) -> RateLimiter {
+        RateLimiter::new(&AppSettings {
+            rate_limits: HashMap::from([(ROUTE.to_owned(), limit)]),
+            ..AppSettings::default()
+        })
+    }
+
+    #[test]
+    fn test_allow_a_burst_up_to_the_limit() {
+        let limiter = limiter(RateLimit::new(3, 60));
+        let now = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            let status = limiter.check(ROUTE, IP, now).unwrap();
+            assert!(status.allowed);
+            assert_eq!(status.remaining, remaining);
+        }
+
+        let status = limiter.check(ROUTE, IP, now).unwrap();
+        assert!(!status.allowed);
+        assert_eq!(status.limit, 3);
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.retry_after_seconds, 20);
+        assert_eq!(status.reset_seconds, 60);
+    }
+
+    #[test]
+    fn test_refill_over_time() {
+        let limiter = limiter(RateLimit::new(2, 10));
+        let now = Instant::now();
+        limiter.check(ROUTE, IP, now);
+        limiter.check(ROUTE, IP, now);
+
+        assert!(!limiter.check(ROUTE, IP, now).unwrap().allowed);
+        assert!(
+            limiter
+                .check(ROUTE, IP, now + Duration::from_secs(5))
+                .unwrap()
+                .allowed
+        );
+
+        // never more than the limit, however long the wait
+        let later = now + Duration::from_secs(3600);
+        let status = limiter.check(ROUTE, IP, later).unwrap();
+        assert_eq!(status.remaining, 1);
+    }
+
+    #[test]
+    fn test_limit_each_client_separately() {
+        let limiter = limiter(RateLimit::new(1, 60));
+        let now = Instant::now();
+        let other_ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        assert!(limiter.check(ROUTE, IP, now).unwrap().allowed);
+        assert!(!limiter.check(ROUTE, IP, now).unwrap().allowed);
+        assert!(limiter.check(ROUTE, other_ip, now).unwrap().allowed);
+    }
+
+    #[test]
+    fn test_limit_ipv6_clients_by_network() {
+        let limiter = limiter(RateLimit::new(1, 60));
+        let now = Instant::now();
+        let ip: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+        let same_network: IpAddr = "2001:db8:1:2:ffff::7".parse().unwrap();
+        let other_network: IpAddr = "2001:db8:1:3::1".parse().unwrap();
+
+        assert!(limiter.check(ROUTE, ip, now).unwrap().allowed);
+        assert!(!limiter.check(ROUTE, same_network, now).unwrap().allowed);
+        assert!(limiter.check(ROUTE, other_network, now).unwrap().allowed);
+    }
+
+    #[test]
+    fn test_limit_ipv4_mapped_clients_as_ipv4() {
+        let limiter = limiter(RateLimit::new(1, 60));
+        let now = Instant::now();
+        let mapped: IpAddr = "::ffff:10.0.0.1".parse().unwrap();
+        let other_mapped: IpAddr = "::ffff:10.0.0.2".parse().unwrap();
+
+        assert!(limiter.check(ROUTE, mapped, now).unwrap().allowed);
+        assert!(
+            !limiter
+                .check(ROUTE, "10.0.0.1".parse().unwrap(), now)
+                .unwrap()
+                .allowed
+        );
+        assert!(limiter.check(ROUTE, other_mapped, now).unwrap().allowed);
+    }
+
+    #[test]
+    fn test_evict_the_least_recently_used_bucket_when_full() {
+        let limiter = limiter(RateLimit::new(2, 3600));
+        let start = Instant::now();
+        let ip = |i: usize| IpAddr::V4(std::net::Ipv4Addr::from(i as u32 + 1));
+
+        // none of them is full again before the end of the test
+        for i in 0..MAX_TRACKED_BUCKETS {
+            limiter.check(ROUTE, ip(i), start + Duration::from_millis(i as u64));
+        }
+        let now = start + Duration::from_secs(60);
+        limiter.check(ROUTE, ip(MAX_TRACKED_BUCKETS), now);
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), MAX_TRACKED_BUCKETS);
+        assert!(!buckets.contains_key(&(ROUTE.to_owned(), ip(0))));
+        assert!(buckets.contains_key(&(ROUTE.to_owned(), ip(1))));
+    }
+
+    #[test]
+    fn test_ignore_routes_without_limit() {
+        let limiter = limiter(RateLimit::new(1, 60));
+
+        assert_eq!(limiter.check("/logout", IP, Instant::now()), None);
+    }
+}
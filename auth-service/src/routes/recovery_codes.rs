@@ -0,0 +1,90 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{data_stores::recovery::RecoveryCode, error::AuthAPIError, user::Email},
+    utils::redact::redacted_debug,
+};
+
+use super::{
+    login::reauthenticate,
+    utils::{
+        map_recovery_code_store_error_to_api_error, map_user_store_error_to_api_error,
+        validate_token_from_cookie_jar, ClientInfo,
+    },
+};
+
+/// Either the password, or a `code` of the authenticator app of TOTP users.
+#[derive(Serialize, Clone, PartialEq, Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    pub password: Option<String>,
+    pub code: Option<String>,
+}
+
+redacted_debug!(RegenerateRecoveryCodesRequest);
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+redacted_debug!(RecoveryCodesResponse);
+
+/// Replaces all recovery codes of the logged in user with a fresh batch. Each
+/// code gets past 2FA, so they must authenticate again: a stolen token alone
+/// must not be enough to mint them.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, email, _) = validate_token_from_cookie_jar(jar, &client, &state).await?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(email.to_owned())
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    if !user.requires_2fa() {
+        return Err(AuthAPIError::BadInput(
+            "2FA is not enabled for this account".into(),
+        ));
+    }
+    reauthenticate(&email, request.password, request.code, &client, &state).await?;
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+/// Generates a batch of recovery codes for the user, invalidating previous
+/// ones. Only their hashes are kept: the returned codes are shown once.
+pub(super) async fn issue_recovery_codes(
+    email: &Email,
+    state: &AppState,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_batch();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(email, codes.iter().map(RecoveryCode::hash).collect())
+        .await
+        .map_err(map_recovery_code_store_error_to_api_error)?;
+
+    Ok(codes
+        .into_iter()
+        .map(|code| code.as_ref().to_owned())
+        .collect())
+}
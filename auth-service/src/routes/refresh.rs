@@ -0,0 +1,123 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit::AuditEventKind,
+        data_stores::{
+            refresh::{RefreshToken, RefreshTokenStoreError},
+            session::{SessionId, SessionStoreError},
+        },
+        error::AuthAPIError,
+        user::Email,
+    },
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    },
+};
+
+use super::utils::{
+    audit, map_refresh_token_store_error_to_api_error, map_session_store_error_to_api_error,
+    map_user_store_error_to_api_error, ClientInfo,
+};
+
+/// Exchanges the refresh token for a new JWT, rotating the refresh token
+/// along the way.
+pub async fn refresh(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let token = match parse_refresh_token_from_cookie_jar(&jar) {
+        Ok(token) => token,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let new_token = RefreshToken::generate();
+    let rotation = state
+        .refresh_token_store
+        .write()
+        .await
+        .rotate(&token, &new_token)
+        .await;
+
+    let (email, session_id) = match rotation {
+        Ok(rotated) => rotated,
+        Err(e) => {
+            if e == RefreshTokenStoreError::TokenReused {
+                tracing::warn!("Refresh token reuse detected, its session has been revoked");
+            }
+            if e != RefreshTokenStoreError::UnexpectedError {
+                audit(&state, AuditEventKind::TokenRejected, None, &client).await;
+            }
+
+            let jar = jar
+                .remove(Cookie::from(JWT_COOKIE_NAME))
+                .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME));
+            return (jar, Err(map_refresh_token_store_error_to_api_error(e)));
+        }
+    };
+
+    // Users with 2FA enabled can only have logged in with it (or proved a
+    // TOTP code when enabling it), which carries over to their new JWT
+    let two_fa = match state.user_store.read().await.get_user(email.clone()).await {
+        Ok(user) => user.requires_2fa(),
+        Err(e) => return (jar, Err(map_user_store_error_to_api_error(e))),
+    };
+
+    match state
+        .session_store
+        .write()
+        .await
+        .touch_session(&session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => return (jar, Err(map_session_store_error_to_api_error(e))),
+    }
+
+    match generate_auth_cookie(
+        &email,
+        &session_id,
+        two_fa,
+        &state.keyring.read().await.active(),
+        &state.settings.jwt,
+    ) {
+        Ok(auth_cookie) => {
+            let jar = jar.add(auth_cookie).add(create_refresh_cookie(&new_token));
+            (jar, Ok(StatusCode::OK))
+        }
+        Err(e) => (jar, Err(AuthAPIError::GenerateTokenError(e))),
+    }
+}
+
+/// Returns the cookie holding the first refresh token of a new session.
+pub(super) async fn issue_refresh_cookie(
+    email: &Email,
+    session_id: &SessionId,
+    state: &AppState,
+) -> Result<Cookie<'static>, AuthAPIError> {
+    let token = RefreshToken::generate();
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .add_token(email, session_id, &token)
+        .await
+        .map_err(map_refresh_token_store_error_to_api_error)?;
+
+    Ok(create_refresh_cookie(&token))
+}
+
+pub(super) fn parse_refresh_token_from_cookie_jar(
+    jar: &CookieJar,
+) -> Result<RefreshToken, AuthAPIError> {
+    let cookie = jar
+        .get(REFRESH_TOKEN_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    RefreshToken::parse(cookie.value().to_owned()).map_err(|_| AuthAPIError::InvalidToken)
+}
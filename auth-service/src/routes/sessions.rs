@@ -0,0 +1,236 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::session::{Session, SessionId},
+        error::AuthAPIError,
+        user::Email,
+    },
+    utils::{
+        auth::{generate_auth_cookie, TOKEN_TTL_SECONDS},
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    },
+};
+
+use super::{
+    refresh::issue_refresh_cookie,
+    utils::{
+        map_refresh_token_store_error_to_api_error, map_session_store_error_to_api_error,
+        map_string_error_to_bad_input_error, validate_token_from_cookie_jar, ClientInfo,
+    },
+};
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    /// RFC 3339 timestamp of the login
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// RFC 3339 timestamp of the last time a JWT was issued for the session
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Whether this is the session of the request
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: &str) -> Self {
+        Self {
+            current: session.id.as_ref() == current_session_id,
+            id: session.id.as_ref().to_owned(),
+            created_at: session
+                .created_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            last_seen_at: session
+                .last_seen_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            user_agent: session.user_agent,
+            ip: session.ip,
+        }
+    }
+}
+
+/// Lists the sessions of the logged in user, ie: where they're logged in.
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, email, claims) = validate_token_from_cookie_jar(jar, &client, &state).await?;
+
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&email)
+        .await
+        .map_err(map_session_store_error_to_api_error)?
+        .into_iter()
+        .map(|session| SessionResponse::new(session, &claims.sid))
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+/// Ends a session of the logged in user: its refresh tokens are revoked, and
+/// its JWTs banned.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, claims) = match validate_token_from_cookie_jar(jar.clone(), &client, &state).await {
+        Ok((_, email, claims)) => (email, claims),
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let id = match SessionId::parse(id) {
+        Ok(id) => id,
+        Err(e) => return (jar, Err(map_string_error_to_bad_input_error(e))),
+    };
+
+    if let Err(e) = end_session(&email, &id, &state).await {
+        return (jar, Err(e));
+    }
+
+    // the user logged themselves out
+    let jar = if id.as_ref() == claims.sid {
+        jar.remove(Cookie::from(JWT_COOKIE_NAME))
+            .remove(Cookie::from(REFRESH_TOKEN_COOKIE_NAME))
+    } else {
+        jar
+    };
+
+    (jar, Ok(StatusCode::OK))
+}
+
+async fn end_session(email: &Email, id: &SessionId, state: &AppState) -> Result<(), AuthAPIError> {
+    state
+        .session_store
+        .write()
+        .await
+        .remove_session(email, id)
+        .await
+        .map_err(map_session_store_error_to_api_error)?;
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(id)
+        .await
+        .map_err(map_refresh_token_store_error_to_api_error)?;
+
+    // no JWT of the session outlives this
+    let banned_until = (Utc::now().timestamp() + TOKEN_TTL_SECONDS)
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add(email, id.as_ref(), banned_until)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = ?e, "Unable to ban tokens of revoked session");
+            AuthAPIError::UnexpectedError
+        })
+}
+
+/// Ends every session of the user but `current_id`, ie: logs them out of
+/// their other devices.
+pub(super) async fn end_other_sessions(
+    email: &Email,
+    current_id: &SessionId,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .map_err(map_session_store_error_to_api_error)?;
+
+    for session in sessions.iter().filter(|session| &session.id != current_id) {
+        end_session(email, &session.id, state).await?;
+    }
+
+    Ok(())
+}
+
+/// Ends every session of the user: the JWTs of each are banned, and all
+/// their refresh tokens revoked. Sessions started afterwards are unaffected,
+/// even within the same second.
+pub(super) async fn end_all_sessions(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .map_err(map_session_store_error_to_api_error)?;
+
+    for session in &sessions {
+        end_session(email, &session.id, state).await?;
+    }
+
+    // refresh tokens outliving their session in the registry, if any
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_all(email)
+        .await
+        .map_err(map_refresh_token_store_error_to_api_error)
+}
+
+/// Records a new session for the user, and adds its JWT and first refresh
+/// token to the cookies.
+pub(super) async fn start_session(
+    email: &Email,
+    two_fa: bool,
+    client: ClientInfo,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<CookieJar, AuthAPIError> {
+    let session = Session::new(email.to_owned(), client.user_agent, client.ip);
+
+    let cookie = generate_auth_cookie(
+        email,
+        &session.id,
+        two_fa,
+        &state.keyring.read().await.active(),
+        &state.settings.jwt,
+    )
+    .map_err(AuthAPIError::GenerateTokenError)?;
+    let refresh_cookie = issue_refresh_cookie(email, &session.id, state).await?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(map_session_store_error_to_api_error)?;
+
+    Ok(jar.add(cookie).add(refresh_cookie))
+}
@@ -4,35 +4,70 @@ use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
     domain::{
+        audit::AuditEventKind,
         error::AuthAPIError,
-        user::{Email, Password, User},
+        user::{Email, HashedPassword, Password, User},
     },
+    utils::redact::redacted_debug,
 };
 
-use super::utils::map_user_store_error_to_api_error;
+use super::{
+    recovery_codes::issue_recovery_codes,
+    utils::{audit, map_user_store_error_to_api_error, ClientInfo},
+    verify_email::send_verification_email,
+};
 
-#[derive(Serialize, PartialEq, Debug, Deserialize)]
+#[derive(Serialize, PartialEq, Deserialize)]
 pub struct SignupResponse {
     pub message: String,
+    /// Only present for accounts with 2FA, to be saved by the user
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
+redacted_debug!(SignupResponse);
+
 pub async fn signup(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let email = Email::parse(request.email).map_err(map_user_store_error_to_api_error)?;
 
     let password = Password::parse(request.password).map_err(map_user_store_error_to_api_error)?;
+    let password = HashedPassword::parse(password, state.settings.password_hashing)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
 
-    let user = User::new(email, password, request.requires_2fa);
-    let mut user_store = state.user_store.write().await;
+    let user = User::new(email.to_owned(), password, request.requires_2fa);
 
-    user_store
+    state
+        .user_store
+        .write()
+        .await
         .add_user(user)
+        .await
         .map_err(map_user_store_error_to_api_error)?;
+    audit(&state, AuditEventKind::Signup, Some(&email), &client).await;
+
+    // the account exists regardless: the user can ask for another email
+    if let Err(e) = send_verification_email(&email, &state).await {
+        tracing::error!(error = ?e, "Unable to send verification email");
+    }
+
+    let recovery_codes = if request.requires_2fa {
+        Some(issue_recovery_codes(&email, &state).await?)
+    } else {
+        None
+    };
 
     let response = Json(SignupResponse {
         message: "User created successfully!".into(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
@@ -0,0 +1,124 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{audit::AuditEventKind, error::AuthAPIError, totp::TotpSecret, user::TwoFAMethod},
+    utils::redact::redacted_debug,
+};
+
+use super::{
+    login::reauthenticate,
+    recovery_codes::{issue_recovery_codes, RecoveryCodesResponse},
+    utils::{
+        audit, map_string_error_to_api_error, map_user_store_error_to_api_error,
+        validate_token_from_cookie_jar, ClientInfo,
+    },
+};
+
+/// Either the password, or a `code` of the current authenticator app when
+/// replacing an active TOTP secret.
+#[derive(Serialize, Clone, Default, PartialEq, Deserialize)]
+pub struct EnrollTotpRequest {
+    pub password: Option<String>,
+    pub code: Option<String>,
+}
+
+redacted_debug!(EnrollTotpRequest);
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+redacted_debug!(EnrollTotpResponse);
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+redacted_debug!(ConfirmTotpRequest);
+
+/// Generates a new TOTP secret for the logged in user. It only becomes their
+/// second factor once confirmed, so a lost enrollment can't lock them out.
+/// Users must authenticate again: a stolen token alone must not be enough to
+/// take over their second factor.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    request: Option<Json<EnrollTotpRequest>>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, email, _) = validate_token_from_cookie_jar(jar, &client, &state).await?;
+    let Json(request) = request.unwrap_or_default();
+    reauthenticate(&email, request.password, request.code, &client, &state).await?;
+
+    let secret = TotpSecret::generate();
+    let otpauth_uri = secret
+        .otpauth_uri(&state.settings.totp_issuer, email.as_ref())
+        .map_err(map_string_error_to_api_error)?;
+
+    let mut user_store = state.user_store.write().await;
+    let mut user = user_store
+        .get_user(email.to_owned())
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    user.pending_totp_secret = Some(secret.clone());
+    user_store
+        .update_user(user)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    let response = Json(EnrollTotpResponse {
+        secret: secret.as_ref().to_owned(),
+        otpauth_uri,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Activates the pending TOTP secret, provided the code matches it, and hands
+/// out a fresh batch of recovery codes in case the app gets lost.
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, email, _) = validate_token_from_cookie_jar(jar, &client, &state).await?;
+
+    let mut user_store = state.user_store.write().await;
+    let mut user = user_store
+        .get_user(email.to_owned())
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    let secret = user
+        .pending_totp_secret
+        .take()
+        .ok_or_else(|| AuthAPIError::BadInput("No pending TOTP enrollment".into()))?;
+
+    let now = state.clock.read().await.now();
+    if !user.accept_totp_code(&secret, &request.code, now) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    user.two_fa_method = TwoFAMethod::Totp(secret);
+    user_store
+        .update_user(user)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    drop(user_store);
+
+    audit(&state, AuditEventKind::TwoFAEnabled, Some(&email), &client).await;
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
@@ -1,4 +1,34 @@
-use crate::domain::{data_stores::user::UserStoreError, error::AuthAPIError};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit::{AuditEvent, AuditEventKind},
+        data_stores::{
+            failed_login::FailedLoginStoreError, one_time::OneTimeTokenStoreError,
+            recovery::RecoveryCodeStoreError, refresh::RefreshTokenStoreError,
+            session::SessionStoreError, twofa::TwoFACodeStoreError, user::UserStoreError,
+        },
+        error::AuthAPIError,
+        user::Email,
+    },
+    utils::{
+        auth::{validate_token, Claims, TokenValidationError},
+        constants::JWT_COOKIE_NAME,
+    },
+};
 
 pub fn map_user_store_error_to_api_error(user_error: UserStoreError) -> AuthAPIError {
     match user_error {
@@ -9,12 +39,293 @@ pub fn map_user_store_error_to_api_error(user_error: UserStoreError) -> AuthAPIE
     }
 }
 
+pub fn map_two_fa_code_store_error_to_api_error(two_fa_error: TwoFACodeStoreError) -> AuthAPIError {
+    match two_fa_error {
+        TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::IncorrectCredentials,
+        TwoFACodeStoreError::CodeExpired => AuthAPIError::TwoFACodeExpired,
+        TwoFACodeStoreError::TooManyAttempts => AuthAPIError::TooManyTwoFAAttempts,
+        TwoFACodeStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+pub fn map_recovery_code_store_error_to_api_error(
+    recovery_error: RecoveryCodeStoreError,
+) -> AuthAPIError {
+    match recovery_error {
+        RecoveryCodeStoreError::InvalidCode => AuthAPIError::IncorrectCredentials,
+        RecoveryCodeStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+pub fn map_refresh_token_store_error_to_api_error(
+    refresh_error: RefreshTokenStoreError,
+) -> AuthAPIError {
+    match refresh_error {
+        RefreshTokenStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        _ => AuthAPIError::InvalidToken,
+    }
+}
+
+pub fn map_session_store_error_to_api_error(session_error: SessionStoreError) -> AuthAPIError {
+    match session_error {
+        SessionStoreError::SessionNotFound => AuthAPIError::SessionNotFound,
+        SessionStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+pub fn map_one_time_token_store_error_to_api_error(
+    one_time_error: OneTimeTokenStoreError,
+) -> AuthAPIError {
+    match one_time_error {
+        OneTimeTokenStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        _ => AuthAPIError::InvalidToken,
+    }
+}
+
+pub fn map_failed_login_store_error_to_api_error(
+    failed_login_error: FailedLoginStoreError,
+) -> AuthAPIError {
+    match failed_login_error {
+        FailedLoginStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+pub fn map_token_validation_error_to_api_error(token_error: TokenValidationError) -> AuthAPIError {
+    match token_error {
+        TokenValidationError::BannedTokenStoreError(e) => {
+            tracing::error!(error = ?e, "Unable to check banned tokens");
+            AuthAPIError::UnexpectedError
+        }
+        _ => AuthAPIError::InvalidToken,
+    }
+}
+
 pub fn map_string_error_to_api_error(str_error: String) -> AuthAPIError {
-    println!("[ERROR] Unexpected generic error. Details: {str_error}");
+    tracing::error!(error = %str_error, "Unexpected generic error");
     AuthAPIError::UnexpectedError
 }
 
 pub fn map_string_error_to_bad_input_error(str_error: String) -> AuthAPIError {
-    println!("[ERROR] Unexpected param. Details: {str_error}");
+    tracing::warn!(error = %str_error, "Unexpected param");
     AuthAPIError::BadInput(str_error)
 }
+
+/// Refuses the request while `until` is ahead, telling the client how many
+/// seconds to wait.
+pub fn throttle_until(until: DateTime<Utc>) -> Result<(), AuthAPIError> {
+    let wait_ms = (until - Utc::now()).num_milliseconds();
+    if wait_ms > 0 {
+        // round up, so that retrying right on time isn't refused again
+        let wait = (wait_ms as u64).div_ceil(1000);
+        return Err(AuthAPIError::TooManyRequests(wait));
+    }
+
+    Ok(())
+}
+
+/// Appends an event to the audit log. Failing to do so is reported, but
+/// never fails the request it's about.
+pub async fn audit(
+    state: &AppState,
+    kind: AuditEventKind,
+    email: Option<&Email>,
+    client: &ClientInfo,
+) {
+    let mut event = AuditEvent::new(kind).client(client.ip.clone(), client.user_agent.clone());
+    if let Some(email) = email {
+        event = event.email(email);
+    }
+
+    if let Err(e) = state.audit_sink.write().await.record(&event).await {
+        tracing::error!(error = ?e, "Unable to record audit event");
+    }
+}
+
+/// Extracts the JWT from the auth cookie and validates it, returning the
+/// cookie, the authenticated email and the token claims. Invalid tokens are
+/// recorded in the audit log.
+pub async fn validate_token_from_cookie_jar(
+    jar: CookieJar,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<(Cookie<'static>, Email, Claims), AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+
+    let claims = match validate_token(
+        cookie.value(),
+        state.keyring.clone(),
+        &state.settings.jwt,
+        state.banned_token_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) => claims,
+        Err(e) => {
+            let error = map_token_validation_error_to_api_error(e);
+            if matches!(error, AuthAPIError::InvalidToken) {
+                audit(state, AuditEventKind::TokenRejected, None, client).await;
+            }
+            return Err(error);
+        }
+    };
+
+    let email = Email::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok((cookie.to_owned(), email, claims))
+}
+
+/// Checks that the caller sent `expected_api_key` as a bearer token. Routes
+/// protected this way are unreachable when no key is configured.
+pub fn authorize_api_key(
+    headers: &HeaderMap,
+    expected_api_key: Option<&str>,
+) -> Result<(), AuthAPIError> {
+    let api_key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    let Some(expected_api_key) = expected_api_key else {
+        tracing::warn!("API key protected route called but no key is configured");
+        return Err(AuthAPIError::IncorrectCredentials);
+    };
+
+    // comparing digests rather than the keys themselves, so that the time
+    // taken doesn't tell how much of the key was right
+    if Sha256::digest(api_key.as_bytes()) != Sha256::digest(expected_api_key.as_bytes()) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    Ok(())
+}
+
+/// Where a request comes from: the address of the client and its
+/// `User-Agent` header.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+        let ip = client_ip(&parts.headers, peer, state.settings.trusted_proxy_hops)
+            .map(|ip| ip.to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+
+        Ok(Self { ip, user_agent })
+    }
+}
+
+/// Address of the client, given that of the peer that connected to us. Behind
+/// `trusted_proxy_hops` proxies, each appending the address it got the request
+/// from to `X-Forwarded-For`, it's the entry that many hops from the end: the
+/// ones before it may have been made up by the client. Falls back to the peer
+/// when the header doesn't go back that far.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted_proxy_hops: usize,
+) -> Option<IpAddr> {
+    if trusted_proxy_hops == 0 {
+        return peer;
+    }
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    forwarded_for
+        .iter()
+        .rev()
+        .nth(trusted_proxy_hops - 1)
+        .and_then(|entry| entry.parse().ok())
+        .or(peer)
+}
+
+// IPv6 clients usually get a whole /64, so they're told apart by it
+const IPV6_CLIENT_PREFIX_BITS: u32 = 64;
+
+/// What clients are told apart by when limiting them: their address, or the
+/// network of IPv6 ones.
+pub fn client_network(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => {
+                let mask = u128::MAX << (128 - IPV6_CLIENT_PREFIX_BITS);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const PEER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+
+        headers
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_without_trusted_proxy() {
+        let headers = forwarded_for(&["1.1.1.1"]);
+
+        assert_eq!(client_ip(&headers, Some(PEER), 0), Some(PEER));
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_hops() {
+        let headers = forwarded_for(&["6.6.6.6, 1.1.1.1", "2.2.2.2"]);
+
+        assert_eq!(
+            client_ip(&headers, Some(PEER), 1),
+            Some("2.2.2.2".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(&headers, Some(PEER), 2),
+            Some("1.1.1.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_client_ip_falls_back_to_peer() {
+        assert_eq!(client_ip(&HeaderMap::new(), Some(PEER), 1), Some(PEER));
+
+        let headers = forwarded_for(&["1.1.1.1"]);
+        assert_eq!(client_ip(&headers, Some(PEER), 2), Some(PEER));
+
+        let headers = forwarded_for(&["not-an-ip"]);
+        assert_eq!(client_ip(&headers, Some(PEER), 1), Some(PEER));
+    }
+}
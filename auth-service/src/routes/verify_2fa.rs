@@ -5,27 +5,56 @@ use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
     domain::{
-        data_stores::twofa::{LoginAttemptId, TwoFACode},
+        audit::AuditEventKind,
+        data_stores::{
+            recovery::{RecoveryCode, RecoveryCodeStoreError},
+            twofa::{LoginAttemptId, TwoFACode},
+            user::UserStoreError,
+        },
         error::AuthAPIError,
-        user::Email,
+        totp::{TotpSecret, TOTP_DIGITS},
+        user::{Email, TwoFAMethod},
     },
-    utils::auth::generate_auth_cookie,
+    utils::redact::redacted_debug,
 };
 
-use super::utils::{map_string_error_to_bad_input_error, map_user_store_error_to_api_error};
+use super::{
+    sessions::start_session,
+    utils::{
+        audit, map_recovery_code_store_error_to_api_error, map_string_error_to_bad_input_error,
+        map_two_fa_code_store_error_to_api_error, map_user_store_error_to_api_error, ClientInfo,
+    },
+};
 
-#[derive(Serialize, Clone, Debug, PartialEq, Deserialize)]
+/// Either `2FACode` or `recoveryCode` must be provided, not both.
+#[derive(Serialize, Clone, PartialEq, Deserialize)]
 pub struct Verify2FARequest {
     email: String,
     #[serde(rename = "loginAttemptId")]
     login_attempt_id: String,
     #[serde(rename = "2FACode")]
-    two_fa_code: String,
+    two_fa_code: Option<String>,
+    #[serde(rename = "recoveryCode")]
+    recovery_code: Option<String>,
+}
+
+redacted_debug!(Verify2FARequest);
+
+#[derive(Serialize, Clone, Debug, PartialEq, Deserialize)]
+pub struct Verify2FAResponse {
+    #[serde(rename = "remainingRecoveryCodes")]
+    pub remaining_recovery_codes: usize,
+}
+
+pub(super) enum SecondFactor {
+    Code(TwoFACode, Option<TotpSecret>),
+    RecoveryCode(RecoveryCode),
 }
 
 pub async fn verify_2fa(
     jar: CookieJar,
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(verify_2fa_token): Json<Verify2FARequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
     let email =
@@ -33,26 +62,179 @@ pub async fn verify_2fa(
     let login_attempt_id = LoginAttemptId::parse(verify_2fa_token.login_attempt_id.clone())
         .map_err(map_string_error_to_bad_input_error)?;
 
-    let two_fa_code = TwoFACode::parse(verify_2fa_token.two_fa_code.clone())
-        .map_err(map_string_error_to_bad_input_error)?;
+    let second_factor = parse_second_factor(
+        &email,
+        verify_2fa_token.two_fa_code,
+        verify_2fa_token.recovery_code,
+        &state,
+    )
+    .await?;
+
+    check_second_factor(&email, &login_attempt_id, second_factor, &client, &state).await?;
+
+    audit(
+        &state,
+        AuditEventKind::LoginSucceeded,
+        Some(&email),
+        &client,
+    )
+    .await;
+    let jar = start_session(&email, true, client, &state, jar).await?;
+
+    let remaining_recovery_codes = state
+        .recovery_code_store
+        .read()
+        .await
+        .remaining_codes(&email)
+        .await
+        .map_err(map_recovery_code_store_error_to_api_error)?;
+
+    let response = Json(Verify2FAResponse {
+        remaining_recovery_codes,
+    });
+
+    Ok((jar, (StatusCode::OK, response)))
+}
+
+/// Checks the second factor against the pending login attempt of the user.
+/// Wrong codes are counted against the attempt. A right one ends it, so that
+/// its code can't be used twice. Either way, the outcome is audited.
+pub(super) async fn check_second_factor(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    second_factor: SecondFactor,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let result = verify_second_factor(email, login_attempt_id, second_factor, state).await;
 
+    let kind = match result {
+        Ok(()) => AuditEventKind::TwoFAVerified,
+        Err(AuthAPIError::UnexpectedError) => return result,
+        Err(_) => AuditEventKind::TwoFAFailed,
+    };
+    audit(state, kind, Some(email), client).await;
+
+    result
+}
+
+async fn verify_second_factor(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    second_factor: SecondFactor,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let code_tuple = two_fa_code_store
-        .get_code(&email)
+    let (stored_login_attempt_id, stored_code) = two_fa_code_store
+        .get_code(email)
         .await
-        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+        .map_err(map_two_fa_code_store_error_to_api_error)?;
 
-    if code_tuple.0 != login_attempt_id || code_tuple.1 != two_fa_code {
+    // Only wrong codes count as failed attempts: without the login attempt ID
+    // (only known to whoever passed the password check), a third party must
+    // not be able to burn through the attempts of a legitimate user.
+    if &stored_login_attempt_id != login_attempt_id {
         return Err(AuthAPIError::IncorrectCredentials);
     }
 
-    let cookie = generate_auth_cookie(&email).map_err(AuthAPIError::GenerateTokenError)?;
-    let jar = jar.add(cookie);
+    let is_valid_code = match second_factor {
+        SecondFactor::Code(two_fa_code, Some(secret)) => {
+            let now = state.clock.read().await.now();
+            // checked and recorded under the same lock, for a code to be
+            // accepted once even by concurrent requests
+            let mut user_store = state.user_store.write().await;
+            let mut user = user_store
+                .get_user(email.to_owned())
+                .await
+                .map_err(map_user_store_error_to_api_error)?;
+
+            let accepted = user.accept_totp_code(&secret, two_fa_code.as_ref(), now);
+            if accepted {
+                user_store
+                    .update_user(user)
+                    .await
+                    .map_err(map_user_store_error_to_api_error)?;
+            }
+
+            accepted
+        }
+        SecondFactor::Code(two_fa_code, None) => stored_code.matches(&two_fa_code),
+        SecondFactor::RecoveryCode(recovery_code) => {
+            match state
+                .recovery_code_store
+                .write()
+                .await
+                .consume_code(email, &recovery_code)
+                .await
+            {
+                Ok(_) => true,
+                Err(RecoveryCodeStoreError::InvalidCode) => false,
+                Err(e) => return Err(map_recovery_code_store_error_to_api_error(e)),
+            }
+        }
+    };
+
+    if !is_valid_code {
+        two_fa_code_store
+            .record_failed_attempt(email)
+            .await
+            .map_err(map_two_fa_code_store_error_to_api_error)?;
+
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
 
     two_fa_code_store
-        .remove_code(&email)
+        .remove_code(email)
         .await
-        .map_err(|_| AuthAPIError::UnexpectedError)?;
+        .map_err(map_two_fa_code_store_error_to_api_error)
+}
+
+pub(super) async fn parse_second_factor(
+    email: &Email,
+    two_fa_code: Option<String>,
+    recovery_code: Option<String>,
+    state: &AppState,
+) -> Result<SecondFactor, AuthAPIError> {
+    match (two_fa_code, recovery_code) {
+        (Some(two_fa_code), None) => {
+            let totp_secret = get_totp_secret(email, state).await?;
+            let code_length = match totp_secret {
+                Some(_) => TOTP_DIGITS,
+                None => state.settings.two_fa_code_length,
+            };
+
+            let two_fa_code = TwoFACode::parse_with_length(two_fa_code, code_length)
+                .map_err(map_string_error_to_bad_input_error)?;
 
-    Ok((jar, StatusCode::OK.into_response()))
+            Ok(SecondFactor::Code(two_fa_code, totp_secret))
+        }
+        (None, Some(recovery_code)) => RecoveryCode::parse(recovery_code)
+            .map(SecondFactor::RecoveryCode)
+            .map_err(map_string_error_to_bad_input_error),
+        _ => Err(AuthAPIError::BadInput(
+            "Expected either a 2FA code or a recovery code".into(),
+        )),
+    }
+}
+
+// Unknown users are treated as using emailed codes: they will be rejected
+// further down, just like anyone without a pending login attempt.
+async fn get_totp_secret(
+    email: &Email,
+    state: &AppState,
+) -> Result<Option<TotpSecret>, AuthAPIError> {
+    match state
+        .user_store
+        .read()
+        .await
+        .get_user(email.to_owned())
+        .await
+    {
+        Ok(user) => match user.two_fa_method {
+            TwoFAMethod::Totp(secret) => Ok(Some(secret)),
+            TwoFAMethod::None | TwoFAMethod::Email => Ok(None),
+        },
+        Err(UserStoreError::UserNotFound) => Ok(None),
+        Err(e) => Err(map_user_store_error_to_api_error(e)),
+    }
 }
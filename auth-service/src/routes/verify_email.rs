@@ -0,0 +1,181 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{
+            one_time::{
+                OneTimeToken, OneTimeTokenPurpose, EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS,
+                EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            },
+            user::UserStoreError,
+        },
+        error::AuthAPIError,
+        user::Email,
+    },
+    utils::redact::redacted_debug,
+};
+
+use super::utils::{
+    map_one_time_token_store_error_to_api_error, map_string_error_to_bad_input_error,
+    map_user_store_error_to_api_error,
+};
+
+#[derive(Serialize, PartialEq, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+redacted_debug!(VerifyEmailRequest);
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, PartialEq, Debug, Deserialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
+
+/// Marks the email address the token was sent to as verified.
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = OneTimeToken::parse(request.token).map_err(map_string_error_to_bad_input_error)?;
+
+    let email = state
+        .one_time_token_store
+        .write()
+        .await
+        .consume_token(OneTimeTokenPurpose::EmailVerification, &token)
+        .await
+        .map_err(map_one_time_token_store_error_to_api_error)?;
+
+    let mut user_store = state.user_store.write().await;
+    let mut user = user_store
+        .get_user(email)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+    user.verified = true;
+    user_store
+        .update_user(user)
+        .await
+        .map_err(map_user_store_error_to_api_error)?;
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email address verified successfully!".into(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Sends a new verification email, in case the previous one got lost or
+/// expired. Nothing is sent for unknown or already verified accounts, and
+/// at most one email per `EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS`. The
+/// response is the same, and takes as long, in every case, so that this
+/// can't be used to find out who has an account.
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(map_user_store_error_to_api_error)?;
+
+    let user = state.user_store.read().await.get_user(email).await;
+    match user {
+        Ok(user) if !user.verified => {
+            let state = state.clone();
+            let resend = async move {
+                if let Err(e) = resend_unless_throttled(&user.email, &state).await {
+                    tracing::error!(error = ?e, "Unable to resend verification email");
+                }
+            };
+            tokio::spawn(resend.in_current_span());
+        }
+        Ok(_) | Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(map_user_store_error_to_api_error(e)),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message:
+            "If an unverified account exists for this email, a verification email was sent to it."
+                .into(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+async fn resend_unless_throttled(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let purpose = OneTimeTokenPurpose::EmailVerification;
+    let cooldown = Duration::try_seconds(EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS)
+        .ok_or(AuthAPIError::UnexpectedError)?;
+
+    // checked and recorded under the same lock, for concurrent requests not
+    // to all get past the cooldown
+    let token = {
+        let mut one_time_token_store = state.one_time_token_store.write().await;
+        let last_issued_at = one_time_token_store
+            .last_issued_at(email, purpose)
+            .await
+            .map_err(map_one_time_token_store_error_to_api_error)?;
+        if last_issued_at.is_some_and(|issued_at| Utc::now() < issued_at + cooldown) {
+            return Ok(());
+        }
+
+        let token = OneTimeToken::generate();
+        one_time_token_store
+            .add_token(email, purpose, &token, Utc::now() + purpose.ttl())
+            .await
+            .map_err(map_one_time_token_store_error_to_api_error)?;
+        token
+    };
+
+    email_verification_token(email, &token, state).await
+}
+
+/// Emails a token proving the user owns the address, replacing any token
+/// sent before.
+pub(super) async fn send_verification_email(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let purpose = OneTimeTokenPurpose::EmailVerification;
+    let token = OneTimeToken::generate();
+
+    state
+        .one_time_token_store
+        .write()
+        .await
+        .add_token(email, purpose, &token, Utc::now() + purpose.ttl())
+        .await
+        .map_err(map_one_time_token_store_error_to_api_error)?;
+
+    email_verification_token(email, &token, state).await
+}
+
+async fn email_verification_token(
+    email: &Email,
+    token: &OneTimeToken,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let email_client = state.email_client.read().await;
+    email_client
+        .send_email(
+            email,
+            "Please verify your email address",
+            &format!(
+                "Welcome! To confirm this email address is yours, use the following token within {} hours: {}",
+                EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600,
+                token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(error = ?e, "Unable to send email");
+            AuthAPIError::UnexpectedError
+        })
+}
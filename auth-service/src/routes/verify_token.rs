@@ -1,22 +1,50 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{audit::AuditEventKind, error::AuthAPIError},
+    utils::{auth::validate_token, redact::redacted_debug},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::utils::{audit, map_token_validation_error_to_api_error, ClientInfo};
+
+#[derive(Serialize, Deserialize)]
 pub struct VerifyTokenResquest {
     token: String,
 }
 
+redacted_debug!(VerifyTokenResquest);
+
+/// Deprecated: tells anyone whether a JWT is valid, with nothing but the
+/// rate limit of the route in the way. Services should use `introspect`,
+/// which requires an API key and tells what the token says. Invalid tokens
+/// still get an empty `401`, as existing clients expect.
 pub async fn verify_token(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(body): Json<VerifyTokenResquest>,
 ) -> impl IntoResponse {
     let VerifyTokenResquest { token } = body;
     let banned_token_store = state.banned_token_store.clone();
 
-    match validate_token(&token, banned_token_store).await {
-        Err(_) => StatusCode::UNAUTHORIZED.into_response(),
+    match validate_token(
+        &token,
+        state.keyring.clone(),
+        &state.settings.jwt,
+        banned_token_store,
+    )
+    .await
+    {
+        Err(e) => {
+            let error = map_token_validation_error_to_api_error(e);
+            if !matches!(error, AuthAPIError::InvalidToken) {
+                return error.into_response();
+            }
+
+            audit(&state, AuditEventKind::TokenRejected, None, &client).await;
+            StatusCode::UNAUTHORIZED.into_response()
+        }
         Ok(_) => StatusCode::OK.into_response(),
     }
 }
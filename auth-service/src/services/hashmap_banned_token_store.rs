@@ -1,27 +1,81 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use chrono::Utc;
 
 use crate::domain::{
-    data_stores::token::{BannedTokenState, BannedTokenStore},
+    data_stores::token::{BannedTokenState, BannedTokenStore, BannedTokenStoreResult},
     user::Email,
 };
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct HashmapBannedTokenStore {
-    // keep a list of tokens per user
-    data: HashMap<String, Email>,
+    // banned token id (jti) -> (owner, expiration timestamp)
+    data: HashMap<String, (Email, usize)>,
+    // min-heap of (expiration timestamp, jti), used to evict expired tokens
+    // without scanning the whole map
+    expirations: BinaryHeap<Reverse<(usize, String)>>,
+}
+
+impl HashmapBannedTokenStore {
+    /// Drops every token whose expiration is at or before `now`.
+    pub fn purge_expired(&mut self, now: usize) {
+        while let Some(Reverse((expires_at, _))) = self.expirations.peek() {
+            if *expires_at > now {
+                break;
+            }
+
+            if let Some(Reverse((expires_at, jti))) = self.expirations.pop() {
+                // the token may have been banned again with a later expiration
+                if self.data.get(&jti).map(|(_, exp)| *exp) == Some(expires_at) {
+                    self.data.remove(&jti);
+                }
+            }
+        }
+    }
+}
+
+fn now() -> usize {
+    Utc::now().timestamp().try_into().unwrap_or_default()
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashmapBannedTokenStore {
-    fn add(&mut self, email: &Email, token: &str) {
-        self.data.insert(token.to_owned(), email.clone());
+    async fn add(
+        &mut self,
+        email: &Email,
+        jti: &str,
+        expires_at: usize,
+    ) -> BannedTokenStoreResult<()> {
+        self.purge_expired(now());
+
+        self.data
+            .insert(jti.to_owned(), (email.clone(), expires_at));
+        self.expirations.push(Reverse((expires_at, jti.to_owned())));
+
+        Ok(())
     }
 
-    fn verify(&self, token: &str) -> BannedTokenState {
-        match self.data.get(token).cloned() {
-            None => BannedTokenState::Absent,
-            Some(email) => BannedTokenState::Exists(email),
-        }
+    async fn verify(&self, jti: &str) -> BannedTokenStoreResult<BannedTokenState> {
+        let state = match self.data.get(jti) {
+            Some((email, expires_at)) if *expires_at > now() => {
+                BannedTokenState::Exists(email.clone())
+            }
+            _ => BannedTokenState::Absent,
+        };
+
+        Ok(state)
+    }
+
+    async fn len(&self) -> BannedTokenStoreResult<usize> {
+        let now = now();
+        Ok(self
+            .data
+            .values()
+            .filter(|(_, expires_at)| *expires_at > now)
+            .count())
     }
 }
 
@@ -31,23 +85,66 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_token_adds_user_and_token() {
-        let token = "sometoken";
+        let jti = "some-jti";
         let email = Email::parse("email@email.com").unwrap();
 
         let mut store = HashmapBannedTokenStore::default();
-        store.add(&email, token);
+        store.add(&email, jti, now() + 600).await.unwrap();
 
-        let result = store.verify(token);
+        let result = store.verify(jti).await.unwrap();
         assert!(result.email().is_some());
     }
 
     #[tokio::test]
     async fn test_user_and_token_not_added() {
-        let token = "sometoken";
+        let jti = "some-jti";
 
         let store = HashmapBannedTokenStore::default();
-        let result = store.verify(token);
+        let result = store.verify(jti).await.unwrap();
 
         assert_eq!(result, BannedTokenState::Absent);
     }
+
+    #[tokio::test]
+    async fn test_expired_token_is_no_longer_reported() {
+        let jti = "some-jti";
+        let email = Email::parse("email@email.com").unwrap();
+
+        let mut store = HashmapBannedTokenStore::default();
+        store.add(&email, jti, now() - 1).await.unwrap();
+
+        assert_eq!(store.verify(jti).await.unwrap(), BannedTokenState::Absent);
+        assert_eq!(store.len().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_tokens_are_evicted() {
+        let email = Email::parse("email@email.com").unwrap();
+        let mut store = HashmapBannedTokenStore::default();
+
+        store.add(&email, "live", now() + 600).await.unwrap();
+        for i in 0..10_000 {
+            store
+                .add(&email, &format!("expired-{i}"), now() - 1)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.len().await.unwrap(), 1);
+        // only the live token and the last expired one (not yet swept) remain
+        assert!(store.data.len() <= 2);
+        assert!(store.expirations.len() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_keeps_rebanned_token() {
+        let email = Email::parse("email@email.com").unwrap();
+        let mut store = HashmapBannedTokenStore::default();
+
+        store.add(&email, "some-jti", 10).await.unwrap();
+        store.add(&email, "some-jti", now() + 600).await.unwrap();
+        store.purge_expired(now());
+
+        assert!(store.verify("some-jti").await.unwrap().exists());
+    }
 }
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::data_stores::failed_login::{
+    FailedLoginKey, FailedLoginStore, FailedLoginStoreError, LockoutPolicy,
+};
+
+// Past that many keys, the stale ones are dropped, then the least recently
+// failed ones (locked ones last) until a tenth of the room is free again
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+#[derive(Clone, Debug)]
+struct FailedLoginEntry {
+    failures: u32,
+    last_failure_at: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+pub struct HashmapFailedLoginStore {
+    entries: HashMap<FailedLoginKey, FailedLoginEntry>,
+    account_policy: LockoutPolicy,
+    ip_policy: LockoutPolicy,
+    max_keys: usize,
+}
+
+impl HashmapFailedLoginStore {
+    pub fn new(account_policy: LockoutPolicy, ip_policy: LockoutPolicy) -> Self {
+        Self {
+            entries: HashMap::new(),
+            account_policy,
+            ip_policy,
+            max_keys: MAX_TRACKED_KEYS,
+        }
+    }
+
+    fn policy(&self, key: &FailedLoginKey) -> &LockoutPolicy {
+        match key {
+            FailedLoginKey::Account(_) => &self.account_policy,
+            FailedLoginKey::Ip(_) => &self.ip_policy,
+        }
+    }
+
+    fn is_stale(&self, key: &FailedLoginKey, entry: &FailedLoginEntry, now: DateTime<Utc>) -> bool {
+        let forgotten_at = entry.last_failure_at + self.policy(key).reset_after;
+        now >= forgotten_at && entry.locked_until.is_none_or(|until| now >= until)
+    }
+
+    // Makes room for a new key
+    fn evict(&mut self, now: DateTime<Utc>) {
+        let stale: Vec<FailedLoginKey> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| self.is_stale(key, entry, now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            self.entries.remove(&key);
+        }
+
+        let target = self.max_keys - self.max_keys.div_ceil(10);
+        if self.entries.len() <= target {
+            return;
+        }
+
+        let mut by_age: Vec<(bool, DateTime<Utc>, FailedLoginKey)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                let locked = entry.locked_until.is_some_and(|until| until > now);
+                (locked, entry.last_failure_at, key.clone())
+            })
+            .collect();
+        let excess = self.entries.len() - target;
+        by_age.select_nth_unstable_by(excess - 1, |a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        for (_, _, key) in by_age.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+impl Default for HashmapFailedLoginStore {
+    fn default() -> Self {
+        Self::new(LockoutPolicy::per_account(), LockoutPolicy::per_ip())
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashmapFailedLoginStore {
+    async fn locked_until(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<DateTime<Utc>>, FailedLoginStoreError> {
+        let now = Utc::now();
+
+        Ok(self
+            .entries
+            .get(key)
+            .and_then(|entry| entry.locked_until)
+            .filter(|until| *until > now))
+    }
+
+    async fn record_failure(
+        &mut self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<DateTime<Utc>>, FailedLoginStoreError> {
+        // start over for the key if its failures are old enough to be
+        // forgotten. Those of other keys are only dropped once room is needed.
+        let now = Utc::now();
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| self.is_stale(key, entry, now))
+        {
+            self.entries.remove(key);
+        }
+        if self.entries.len() >= self.max_keys && !self.entries.contains_key(key) {
+            self.evict(now);
+        }
+
+        let policy = *self.policy(key);
+        let entry = self.entries.entry(key.clone()).or_insert(FailedLoginEntry {
+            failures: 0,
+            last_failure_at: now,
+            locked_until: None,
+        });
+
+        entry.failures = entry.failures.saturating_add(1);
+        entry.last_failure_at = now;
+        if let Some(lockout) = policy.lockout_after(entry.failures) {
+            entry.locked_until = Some(now + lockout);
+        }
+
+        Ok(entry.locked_until.filter(|until| *until > now))
+    }
+
+    async fn reset(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::domain::user::Email;
+
+    use super::*;
+
+    fn store_locking_after(max_failures: u32) -> HashmapFailedLoginStore {
+        let policy = LockoutPolicy::new(max_failures);
+        HashmapFailedLoginStore::new(policy, policy)
+    }
+
+    fn account() -> FailedLoginKey {
+        FailedLoginKey::Account(Email::default())
+    }
+
+    #[tokio::test]
+    async fn test_lock_once_limit_reached() {
+        let mut store = store_locking_after(3);
+        let key = account();
+
+        assert_eq!(store.record_failure(&key).await, Ok(None));
+        assert_eq!(store.record_failure(&key).await, Ok(None));
+        assert_eq!(store.locked_until(&key).await, Ok(None));
+
+        let locked_until = store.record_failure(&key).await.unwrap();
+        assert!(locked_until.is_some_and(|until| until > Utc::now()));
+        assert_eq!(store.locked_until(&key).await, Ok(locked_until));
+    }
+
+    #[tokio::test]
+    async fn test_lock_longer_with_each_failure() {
+        let mut store = store_locking_after(1);
+        let key = account();
+
+        let first_lockout = store.record_failure(&key).await.unwrap().unwrap();
+        let second_lockout = store.record_failure(&key).await.unwrap().unwrap();
+
+        // doubled, give or take the time elapsed between both calls
+        let base_lockout = LockoutPolicy::new(1).base_lockout;
+        assert!(second_lockout - first_lockout > base_lockout - Duration::seconds(1));
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let mut store = store_locking_after(1);
+        let key = account();
+        store.record_failure(&key).await.unwrap();
+
+        store.reset(&key).await.unwrap();
+
+        assert_eq!(store.locked_until(&key).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_counted_separately() {
+        let mut store = store_locking_after(1);
+        let key = account();
+        let other_account = FailedLoginKey::Account(Email::parse("other@email.com").unwrap());
+        let ip = FailedLoginKey::Ip("127.0.0.1".into());
+
+        store.record_failure(&key).await.unwrap();
+
+        assert_eq!(store.locked_until(&other_account).await, Ok(None));
+        assert_eq!(store.locked_until(&ip).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_apply_the_policy_of_the_key() {
+        let mut store = HashmapFailedLoginStore::new(LockoutPolicy::new(1), LockoutPolicy::new(2));
+        let ip = FailedLoginKey::Ip("127.0.0.1".into());
+
+        assert_eq!(store.record_failure(&ip).await, Ok(None));
+        assert!(store.record_failure(&ip).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_lockout_expires() {
+        let policy = LockoutPolicy {
+            base_lockout: Duration::milliseconds(50),
+            ..LockoutPolicy::new(1)
+        };
+        let mut store = HashmapFailedLoginStore::new(policy, policy);
+        let key = account();
+        store.record_failure(&key).await.unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+
+        assert_eq!(store.locked_until(&key).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_forget_old_failures() {
+        let policy = LockoutPolicy {
+            reset_after: Duration::zero(),
+            ..LockoutPolicy::new(2)
+        };
+        let mut store = HashmapFailedLoginStore::new(policy, policy);
+        let key = account();
+
+        // each failure is forgotten by the time the next one happens
+        assert_eq!(store.record_failure(&key).await, Ok(None));
+        assert_eq!(store.record_failure(&key).await, Ok(None));
+        assert_eq!(store.record_failure(&key).await, Ok(None));
+    }
+
+    fn ip(i: usize) -> FailedLoginKey {
+        FailedLoginKey::Ip(format!("10.0.0.{i}"))
+    }
+
+    #[tokio::test]
+    async fn test_evict_the_least_recently_failed_keys_when_full() {
+        let mut store = HashmapFailedLoginStore {
+            max_keys: 20,
+            ..store_locking_after(2)
+        };
+        for i in 0..20 {
+            store.record_failure(&ip(i)).await.unwrap();
+        }
+
+        // a tenth of the room is made at once
+        store.record_failure(&ip(20)).await.unwrap();
+
+        assert_eq!(store.entries.len(), 19);
+        assert!(!store.entries.contains_key(&ip(0)));
+        assert!(!store.entries.contains_key(&ip(1)));
+        assert!(store.entries.contains_key(&ip(2)));
+        assert!(store.entries.contains_key(&ip(20)));
+    }
+
+    #[tokio::test]
+    async fn test_keep_locked_keys_when_full() {
+        let mut store = HashmapFailedLoginStore {
+            max_keys: 10,
+            ..store_locking_after(2)
+        };
+        let locked = account();
+        store.record_failure(&locked).await.unwrap();
+        store.record_failure(&locked).await.unwrap();
+        for i in 0..10 {
+            store.record_failure(&ip(i)).await.unwrap();
+        }
+
+        assert!(store.entries.len() <= 10);
+        assert!(store.locked_until(&locked).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_drop_stale_keys_first_when_full() {
+        let policy = LockoutPolicy {
+            reset_after: Duration::zero(),
+            ..LockoutPolicy::new(2)
+        };
+        let mut store = HashmapFailedLoginStore {
+            max_keys: 10,
+            ..HashmapFailedLoginStore::new(policy, policy)
+        };
+        for i in 0..11 {
+            store.record_failure(&ip(i)).await.unwrap();
+        }
+
+        // all the others were already forgotten
+        assert_eq!(store.entries.len(), 1);
+        assert!(store.entries.contains_key(&ip(10)));
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::{
+    data_stores::one_time::{
+        OneTimeToken, OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError,
+    },
+    user::Email,
+};
+
+#[derive(Clone, Debug)]
+struct OneTimeTokenEntry {
+    email: Email,
+    purpose: OneTimeTokenPurpose,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Tokens are keyed by their hash.
+#[derive(Default)]
+pub struct HashmapOneTimeTokenStore {
+    tokens: HashMap<String, OneTimeTokenEntry>,
+}
+
+#[async_trait::async_trait]
+impl OneTimeTokenStore for HashmapOneTimeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        purpose: OneTimeTokenPurpose,
+        token: &OneTimeToken,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), OneTimeTokenStoreError> {
+        // drop expired tokens, along with the previous one of the user
+        let now = Utc::now();
+        self.tokens.retain(|_, entry| {
+            entry.expires_at > now && !(&entry.email == email && entry.purpose == purpose)
+        });
+
+        let entry = OneTimeTokenEntry {
+            email: email.to_owned(),
+            purpose,
+            issued_at: now,
+            expires_at,
+        };
+        self.tokens.insert(token.hash(), entry);
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        purpose: OneTimeTokenPurpose,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError> {
+        let hash = token.hash();
+        match self.tokens.get(&hash) {
+            Some(entry) if entry.purpose == purpose => {}
+            _ => return Err(OneTimeTokenStoreError::TokenNotFound),
+        }
+
+        let entry = self
+            .tokens
+            .remove(&hash)
+            .ok_or(OneTimeTokenStoreError::TokenNotFound)?;
+        if Utc::now() >= entry.expires_at {
+            return Err(OneTimeTokenStoreError::TokenExpired);
+        }
+
+        Ok(entry.email)
+    }
+
+    async fn last_issued_at(
+        &self,
+        email: &Email,
+        purpose: OneTimeTokenPurpose,
+    ) -> Result<Option<DateTime<Utc>>, OneTimeTokenStoreError> {
+        // at most one token per user and purpose
+        Ok(self
+            .tokens
+            .values()
+            .find(|entry| &entry.email == email && entry.purpose == purpose)
+            .map(|entry| entry.issued_at))
+    }
+
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), OneTimeTokenStoreError> {
+        self.tokens.retain(|_, entry| &entry.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    const PURPOSE: OneTimeTokenPurpose = OneTimeTokenPurpose::PasswordReset;
+
+    async fn store_with_token(
+        email: &Email,
+        expires_at: DateTime<Utc>,
+    ) -> (HashmapOneTimeTokenStore, OneTimeToken) {
+        let mut store = HashmapOneTimeTokenStore::default();
+        let token = OneTimeToken::generate();
+
+        store
+            .add_token(email, PURPOSE, &token, expires_at)
+            .await
+            .unwrap();
+        (store, token)
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_only_once() {
+        let email = Email::default();
+        let (mut store, token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+
+        assert_eq!(store.consume_token(PURPOSE, &token).await, Ok(email));
+        assert_eq!(
+            store.consume_token(PURPOSE, &token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_unknown_token() {
+        let mut store = HashmapOneTimeTokenStore::default();
+
+        assert_eq!(
+            store
+                .consume_token(PURPOSE, &OneTimeToken::generate())
+                .await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_token() {
+        let email = Email::default();
+        let (mut store, token) = store_with_token(&email, Utc::now() - Duration::seconds(1)).await;
+
+        assert_eq!(
+            store.consume_token(PURPOSE, &token).await,
+            Err(OneTimeTokenStoreError::TokenExpired)
+        );
+        assert_eq!(
+            store.consume_token(PURPOSE, &token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_token_replaces_previous_one() {
+        let email = Email::default();
+        let (mut store, old_token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+
+        let new_token = OneTimeToken::generate();
+        store
+            .add_token(&email, PURPOSE, &new_token, Utc::now() + PURPOSE.ttl())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.consume_token(PURPOSE, &old_token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(store.consume_token(PURPOSE, &new_token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_of_another_purpose() {
+        let email = Email::default();
+        let (mut store, token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+
+        assert_eq!(
+            store
+                .consume_token(OneTimeTokenPurpose::EmailVerification, &token)
+                .await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+        // the token is left usable for its own purpose
+        assert_eq!(store.consume_token(PURPOSE, &token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_last_issued_at() {
+        let email = Email::default();
+        let before = Utc::now();
+        let (mut store, token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+
+        let issued_at = store.last_issued_at(&email, PURPOSE).await.unwrap();
+        assert!(issued_at.is_some_and(|issued_at| issued_at >= before));
+        assert_eq!(
+            store
+                .last_issued_at(&email, OneTimeTokenPurpose::EmailVerification)
+                .await,
+            Ok(None)
+        );
+
+        store.consume_token(PURPOSE, &token).await.unwrap();
+        assert_eq!(store.last_issued_at(&email, PURPOSE).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_add_token_keeps_tokens_of_other_users() {
+        let email = Email::default();
+        let other_email = Email::parse("other@email.com").unwrap();
+        let (mut store, token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+
+        store
+            .add_token(
+                &other_email,
+                PURPOSE,
+                &OneTimeToken::generate(),
+                Utc::now() + PURPOSE.ttl(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(store.consume_token(PURPOSE, &token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_remove_tokens() {
+        let email = Email::default();
+        let other_email = Email::parse("other@email.com").unwrap();
+        let (mut store, token) = store_with_token(&email, Utc::now() + PURPOSE.ttl()).await;
+        let other_token = OneTimeToken::generate();
+        store
+            .add_token(
+                &other_email,
+                PURPOSE,
+                &other_token,
+                Utc::now() + PURPOSE.ttl(),
+            )
+            .await
+            .unwrap();
+
+        store.remove_tokens(&email).await.unwrap();
+
+        assert_eq!(
+            store.consume_token(PURPOSE, &token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.consume_token(PURPOSE, &other_token).await,
+            Ok(other_email)
+        );
+    }
+}
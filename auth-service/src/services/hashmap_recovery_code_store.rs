@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{
+    data_stores::recovery::{
+        HashedRecoveryCode, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError,
+    },
+    user::Email,
+};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    codes: HashMap<Email, HashSet<HashedRecoveryCode>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<HashedRecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes
+            .insert(email.to_owned(), codes.into_iter().collect());
+        Ok(())
+    }
+
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<usize, RecoveryCodeStoreError> {
+        let codes = self
+            .codes
+            .get_mut(email)
+            .ok_or(RecoveryCodeStoreError::InvalidCode)?;
+
+        if !codes.remove(&code.hash()) {
+            return Err(RecoveryCodeStoreError::InvalidCode);
+        }
+
+        Ok(codes.len())
+    }
+
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.get(email).map_or(0, HashSet::len))
+    }
+
+    async fn remove_codes(&mut self, email: &Email) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn store_with_codes(email: &Email) -> (HashmapRecoveryCodeStore, Vec<RecoveryCode>) {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let codes = RecoveryCode::generate_batch();
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+
+        store.set_codes(email, hashes).await.unwrap();
+        (store, codes)
+    }
+
+    #[tokio::test]
+    async fn test_consume_code_only_once() {
+        let email = Email::default();
+        let (mut store, codes) = store_with_codes(&email).await;
+
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Ok(codes.len() - 1)
+        );
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+        assert_eq!(store.remaining_codes(&email).await, Ok(codes.len() - 1));
+    }
+
+    #[tokio::test]
+    async fn test_consume_unknown_code() {
+        let email = Email::default();
+        let (mut store, codes) = store_with_codes(&email).await;
+
+        assert_eq!(
+            store.consume_code(&email, &RecoveryCode::generate()).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+        assert_eq!(
+            store
+                .consume_code(&Email::parse("other@email.com").unwrap(), &codes[0])
+                .await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_codes_replaces_previous_ones() {
+        let email = Email::default();
+        let (mut store, old_codes) = store_with_codes(&email).await;
+
+        let new_code = RecoveryCode::generate();
+        store
+            .set_codes(&email, vec![new_code.hash()])
+            .await
+            .unwrap();
+
+        assert_eq!(store.remaining_codes(&email).await, Ok(1));
+        assert!(store.consume_code(&email, &old_codes[0]).await.is_err());
+        assert_eq!(store.consume_code(&email, &new_code).await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn test_remove_codes() {
+        let email = Email::default();
+        let (mut store, codes) = store_with_codes(&email).await;
+
+        store.remove_codes(&email).await.unwrap();
+
+        assert_eq!(store.remaining_codes(&email).await, Ok(0));
+        assert!(store.consume_code(&email, &codes[0]).await.is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::domain::{
+    data_stores::refresh::{
+        RefreshToken, RefreshTokenFamilyId, RefreshTokenStore, RefreshTokenStoreError,
+        REFRESH_TOKEN_TTL_SECONDS,
+    },
+    user::Email,
+};
+
+#[derive(Clone, Debug)]
+struct RefreshTokenEntry {
+    email: Email,
+    family_id: RefreshTokenFamilyId,
+    expires_at: DateTime<Utc>,
+    used: bool,
+}
+
+/// Tokens are keyed by their hash. Used tokens are kept until they expire,
+/// so that replaying them can be detected.
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, RefreshTokenEntry>,
+    ttl: Duration,
+}
+
+impl HashmapRefreshTokenStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            tokens: HashMap::new(),
+            ttl,
+        }
+    }
+
+    fn insert(&mut self, email: &Email, family_id: &RefreshTokenFamilyId, token: &RefreshToken) {
+        let entry = RefreshTokenEntry {
+            email: email.to_owned(),
+            family_id: family_id.to_owned(),
+            expires_at: Utc::now() + self.ttl,
+            used: false,
+        };
+
+        self.tokens.insert(token.hash(), entry);
+    }
+
+    fn remove_family(&mut self, family_id: &RefreshTokenFamilyId) {
+        self.tokens.retain(|_, entry| &entry.family_id != family_id);
+    }
+}
+
+impl Default for HashmapRefreshTokenStore {
+    fn default() -> Self {
+        Self::new(
+            Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS).expect("valid refresh token TTL"),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        family_id: &RefreshTokenFamilyId,
+        token: &RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // drop tokens of sessions nobody came back to
+        let now = Utc::now();
+        self.tokens.retain(|_, entry| entry.expires_at > now);
+
+        self.insert(email, family_id, token);
+        Ok(())
+    }
+
+    async fn rotate(
+        &mut self,
+        token: &RefreshToken,
+        new_token: &RefreshToken,
+    ) -> Result<(Email, RefreshTokenFamilyId), RefreshTokenStoreError> {
+        let entry = self
+            .tokens
+            .get_mut(&token.hash())
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if entry.used {
+            let family_id = entry.family_id.clone();
+            self.remove_family(&family_id);
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        if Utc::now() >= entry.expires_at {
+            return Err(RefreshTokenStoreError::TokenExpired);
+        }
+
+        entry.used = true;
+        let (email, family_id) = (entry.email.clone(), entry.family_id.clone());
+        self.insert(&email, &family_id, new_token);
+
+        Ok((email, family_id))
+    }
+
+    async fn revoke(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let family_id = self
+            .tokens
+            .get(&token.hash())
+            .map(|entry| entry.family_id.clone())
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        self.remove_family(&family_id);
+        Ok(())
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.remove_family(family_id);
+        Ok(())
+    }
+
+    async fn revoke_all(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.retain(|_, entry| &entry.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn store_with_token() -> (HashmapRefreshTokenStore, RefreshTokenFamilyId, RefreshToken) {
+        let mut store = HashmapRefreshTokenStore::default();
+        let family_id = RefreshTokenFamilyId::default();
+        let token = RefreshToken::generate();
+
+        store
+            .add_token(&Email::default(), &family_id, &token)
+            .await
+            .unwrap();
+
+        (store, family_id, token)
+    }
+
+    #[tokio::test]
+    async fn test_rotate_token() {
+        let (mut store, family_id, token) = store_with_token().await;
+        let new_token = RefreshToken::generate();
+
+        assert_eq!(
+            store.rotate(&token, &new_token).await,
+            Ok((Email::default(), family_id.clone()))
+        );
+        assert_eq!(
+            store.rotate(&new_token, &RefreshToken::generate()).await,
+            Ok((Email::default(), family_id))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotate_unknown_token() {
+        let (mut store, _, _) = store_with_token().await;
+
+        assert_eq!(
+            store
+                .rotate(&RefreshToken::generate(), &RefreshToken::generate())
+                .await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reused_token_revokes_family() {
+        let (mut store, _, token) = store_with_token().await;
+        let new_token = RefreshToken::generate();
+
+        // another session of the same user must not be affected
+        let other_token = RefreshToken::generate();
+        store
+            .add_token(
+                &Email::default(),
+                &RefreshTokenFamilyId::default(),
+                &other_token,
+            )
+            .await
+            .unwrap();
+
+        store.rotate(&token, &new_token).await.unwrap();
+        assert_eq!(
+            store.rotate(&token, &RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenReused)
+        );
+
+        // the descendant of the replayed token is revoked as well
+        assert_eq!(
+            store.rotate(&new_token, &RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store
+            .rotate(&other_token, &RefreshToken::generate())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_rejected() {
+        let mut store = HashmapRefreshTokenStore::new(Duration::zero());
+        let token = RefreshToken::generate();
+        store
+            .add_token(&Email::default(), &RefreshTokenFamilyId::default(), &token)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.rotate(&token, &RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let (mut store, _, token) = store_with_token().await;
+        let new_token = RefreshToken::generate();
+        store.rotate(&token, &new_token).await.unwrap();
+
+        // revoking with any token of the family revokes all of them
+        store.revoke(&token).await.unwrap();
+
+        assert_eq!(
+            store.rotate(&new_token, &RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.revoke(&token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family_by_id() {
+        let (mut store, family_id, token) = store_with_token().await;
+        let (_, other_family_id, other_token) = store_with_token().await;
+        assert_ne!(family_id, other_family_id);
+        store
+            .add_token(&Email::default(), &other_family_id, &other_token)
+            .await
+            .unwrap();
+
+        store.revoke_family(&family_id).await.unwrap();
+
+        assert_eq!(
+            store.rotate(&token, &RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store
+            .rotate(&other_token, &RefreshToken::generate())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = Email::parse("some@email.com").unwrap();
+        let other_email = Email::parse("other@email.com").unwrap();
+        let tokens = [RefreshToken::generate(), RefreshToken::generate()];
+        let other_token = RefreshToken::generate();
+
+        for token in &tokens {
+            store
+                .add_token(&email, &RefreshTokenFamilyId::default(), token)
+                .await
+                .unwrap();
+        }
+        store
+            .add_token(&other_email, &RefreshTokenFamilyId::default(), &other_token)
+            .await
+            .unwrap();
+
+        store.revoke_all(&email).await.unwrap();
+
+        for token in &tokens {
+            assert_eq!(
+                store.rotate(token, &RefreshToken::generate()).await,
+                Err(RefreshTokenStoreError::TokenNotFound)
+            );
+        }
+        assert!(store
+            .rotate(&other_token, &RefreshToken::generate())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_tokens_are_evicted_on_add() {
+        let mut store = HashmapRefreshTokenStore::new(Duration::zero());
+        for _ in 0..100 {
+            store
+                .add_token(
+                    &Email::default(),
+                    &RefreshTokenFamilyId::default(),
+                    &RefreshToken::generate(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.tokens.len(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+
+use crate::domain::{
+    data_stores::{
+        refresh::REFRESH_TOKEN_TTL_SECONDS,
+        session::{Session, SessionId, SessionStore, SessionStoreError},
+    },
+    user::Email,
+};
+
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+    ttl: Duration,
+}
+
+impl HashmapSessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            ttl,
+        }
+    }
+
+    fn is_live(&self, session: &Session) -> bool {
+        session.last_seen_at + self.ttl > Utc::now()
+    }
+}
+
+impl Default for HashmapSessionStore {
+    fn default() -> Self {
+        Self::new(
+            Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS).expect("valid refresh token TTL"),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        // drop sessions nobody came back to
+        let expired: Vec<SessionId> = self
+            .sessions
+            .values()
+            .filter(|session| !self.is_live(session))
+            .map(|session| session.id.clone())
+            .collect();
+        for id in expired {
+            self.sessions.remove(&id);
+        }
+
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+
+        session.last_seen_at = Utc::now();
+        Ok(())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| &session.email == email && self.is_live(session))
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+
+        Ok(sessions)
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &SessionId,
+    ) -> Result<Session, SessionStoreError> {
+        match self.sessions.get(id) {
+            Some(session) if &session.email == email => {}
+            _ => return Err(SessionStoreError::SessionNotFound),
+        }
+
+        self.sessions
+            .remove(id)
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| &session.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(email: &str) -> Session {
+        Session::new(
+            Email::parse(email).unwrap(),
+            Some("Mozilla/5.0".to_owned()),
+            Some("127.0.0.1".to_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_sessions_of_user() {
+        let mut store = HashmapSessionStore::default();
+        let first = session("some@email.com");
+        let second = session("some@email.com");
+        let other = session("other@email.com");
+
+        for session in [&first, &second, &other] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        let sessions = store.get_sessions(&first.email).await.unwrap();
+        assert_eq!(sessions, vec![first, second]);
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = session("some@email.com");
+        let other_email = Email::parse("other@email.com").unwrap();
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(
+            store.remove_session(&other_email, &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(
+            store.remove_session(&session.email, &session.id).await,
+            Ok(session.clone())
+        );
+        assert_eq!(
+            store.remove_session(&session.email, &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let session = session("some@email.com");
+        let other = self::session("other@email.com");
+        store.add_session(session.clone()).await.unwrap();
+        store.add_session(other.clone()).await.unwrap();
+
+        store.remove_sessions(&session.email).await.unwrap();
+
+        assert!(store.get_sessions(&session.email).await.unwrap().is_empty());
+        assert_eq!(store.get_sessions(&other.email).await.unwrap(), vec![other]);
+    }
+
+    #[tokio::test]
+    async fn test_touch_session_keeps_it_alive() {
+        let mut store = HashmapSessionStore::new(Duration::seconds(60));
+        let mut session = session("some@email.com");
+        session.last_seen_at -= Duration::seconds(120);
+        store.add_session(session.clone()).await.unwrap();
+
+        assert!(store.get_sessions(&session.email).await.unwrap().is_empty());
+
+        store.touch_session(&session.id).await.unwrap();
+        assert_eq!(store.get_sessions(&session.email).await.unwrap().len(), 1);
+        assert_eq!(
+            store.touch_session(&SessionId::default()).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+}
@@ -1,13 +1,50 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Duration, Utc};
+
 use crate::domain::{
-    data_stores::twofa::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::twofa::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, MAX_TWO_FA_ATTEMPTS,
+        TWO_FA_CODE_TTL_SECONDS,
+    },
     user::Email,
 };
 
-#[derive(Default)]
+#[derive(Clone, Debug)]
+struct TwoFACodeEntry {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    created_at: DateTime<Utc>,
+    failed_attempts: u32,
+}
+
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<Email, TwoFACodeEntry>,
+    ttl: Duration,
+    max_attempts: u32,
+}
+
+impl HashmapTwoFACodeStore {
+    pub fn new(ttl: Duration, max_attempts: u32) -> Self {
+        Self {
+            codes: HashMap::new(),
+            ttl,
+            max_attempts,
+        }
+    }
+
+    fn is_expired(&self, entry: &TwoFACodeEntry) -> bool {
+        Utc::now() >= entry.created_at + self.ttl
+    }
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        Self::new(
+            Duration::try_seconds(TWO_FA_CODE_TTL_SECONDS).expect("valid 2FA code TTL"),
+            MAX_TWO_FA_ATTEMPTS,
+        )
+    }
 }
 
 #[async_trait::async_trait]
@@ -18,10 +55,25 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = email.clone();
-        let value = (login_attempt_id.clone(), code.clone());
+        // drop codes nobody came back for
+        let expired: Vec<Email> = self
+            .codes
+            .iter()
+            .filter(|(_, entry)| self.is_expired(entry))
+            .map(|(email, _)| email.clone())
+            .collect();
+        for email in expired {
+            self.codes.remove(&email);
+        }
 
-        self.codes.insert(key, value);
+        let entry = TwoFACodeEntry {
+            login_attempt_id,
+            code,
+            created_at: Utc::now(),
+            failed_attempts: 0,
+        };
+
+        self.codes.insert(email, entry);
 
         Ok(())
     }
@@ -34,14 +86,36 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
             .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
             .and(Ok(()))
     }
+
     async fn get_code(
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        self.codes
+        let entry = self
+            .codes
             .get(email)
-            .cloned()
-            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if self.is_expired(entry) {
+            return Err(TwoFACodeStoreError::CodeExpired);
+        }
+
+        Ok((entry.login_attempt_id.clone(), entry.code.clone()))
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let entry = self
+            .codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        entry.failed_attempts += 1;
+        if entry.failed_attempts >= self.max_attempts {
+            self.codes.remove(email);
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
+        Ok(())
     }
 }
 
@@ -96,4 +170,90 @@ mod tests {
         let result = store.get_code(&email).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_expired_code_is_rejected() {
+        let email = Email::default();
+
+        let mut store = HashmapTwoFACodeStore::new(Duration::zero(), MAX_TWO_FA_ATTEMPTS);
+        store
+            .add_code(
+                email.to_owned(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_code(&email).await.unwrap_err(),
+            TwoFACodeStoreError::CodeExpired
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_codes_are_evicted_on_add() {
+        let mut store = HashmapTwoFACodeStore::new(Duration::zero(), MAX_TWO_FA_ATTEMPTS);
+        for i in 0..100 {
+            let email = Email::parse(format!("user{i}@email.com")).unwrap();
+            store
+                .add_code(email, LoginAttemptId::default(), TwoFACode::default())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.codes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_attempt_is_invalidated_after_too_many_failures() {
+        let email = Email::default();
+
+        let mut store = HashmapTwoFACodeStore::new(Duration::try_minutes(10).unwrap(), 3);
+        store
+            .add_code(
+                email.to_owned(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert_eq!(
+            store.record_failed_attempt(&email).await.unwrap_err(),
+            TwoFACodeStoreError::TooManyAttempts
+        );
+        assert_eq!(
+            store.get_code(&email).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_code_resets_failed_attempts() {
+        let email = Email::default();
+
+        let mut store = HashmapTwoFACodeStore::new(Duration::try_minutes(10).unwrap(), 2);
+        store
+            .add_code(
+                email.to_owned(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+
+        store
+            .add_code(
+                email.to_owned(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+    }
 }
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::domain::{
     data_stores::user::{UserStore, UserStoreError, UserStoreResult},
-    user::{Email, Password, User},
+    user::{Email, HashedPassword, Password, User},
 };
 
 #[derive(Default)]
@@ -12,9 +12,9 @@ pub struct HashmapUserStore {
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    fn add_user(&mut self, user: User) -> UserStoreResult<()> {
+    async fn add_user(&mut self, user: User) -> UserStoreResult<()> {
         let email = user.clone().email;
-        if self.get_user(email.to_owned()).is_ok() {
+        if self.get_user(email.to_owned()).await.is_ok() {
             return Err(UserStoreError::UserAlreadyExists);
         }
 
@@ -22,44 +22,85 @@ impl UserStore for HashmapUserStore {
         Ok(())
     }
 
-    fn get_user(&self, email: Email) -> UserStoreResult<User> {
+    async fn get_user(&self, email: Email) -> UserStoreResult<User> {
         self.users
             .get(&email)
             .cloned()
             .ok_or(UserStoreError::UserNotFound)
     }
 
-    fn validate_user(&self, email: Email, password: Password) -> UserStoreResult<()> {
-        let user = self.get_user(email)?;
-        if user.password != password {
-            return Err(UserStoreError::InvalidCredentials(
-                "Passwords do not match".into(),
-            ));
-        }
+    async fn validate_user(&self, email: Email, password: Password) -> UserStoreResult<()> {
+        let user = self.get_user(email).await?;
+        user.password.verify_raw_password(&password).await
+    }
+
+    async fn update_password(
+        &mut self,
+        email: Email,
+        password: HashedPassword,
+    ) -> UserStoreResult<()> {
+        let user = self
+            .users
+            .get_mut(&email)
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        user.password = password;
+        Ok(())
+    }
+
+    async fn update_user(&mut self, user: User) -> UserStoreResult<()> {
+        let stored_user = self
+            .users
+            .get_mut(&user.email)
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        *stored_user = user;
         Ok(())
     }
+
+    async fn delete_user(&mut self, email: &Email) -> UserStoreResult<()> {
+        self.users
+            .remove(email)
+            .map(|_| ())
+            .ok_or(UserStoreError::UserNotFound)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::user::{Email, Password};
+    use crate::domain::{
+        totp::TotpSecret,
+        user::{Email, HashedPassword, Password, PasswordHashingParams, TwoFAMethod},
+    };
 
     use super::*;
 
+    async fn hash(password: &str) -> HashedPassword {
+        HashedPassword::parse(
+            Password::parse(password).unwrap(),
+            PasswordHashingParams::default(),
+        )
+        .await
+        .unwrap()
+    }
+
     #[tokio::test]
     async fn test_add_user() {
         let mut store = HashmapUserStore::default();
         let user = User {
             email: Email::parse("some@email.com").unwrap(),
-            password: Password::parse("password").unwrap(),
-            requires_2fa: true,
+            password: hash("password").await,
+            two_fa_method: TwoFAMethod::Email,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
         };
         let other_user = user.clone();
 
-        let result = store.add_user(user);
+        let result = store.add_user(user).await;
         assert!(result.is_ok());
 
-        let result = store.add_user(other_user);
+        let result = store.add_user(other_user).await;
         assert_eq!(result.err().unwrap(), UserStoreError::UserAlreadyExists)
     }
 
@@ -69,16 +110,20 @@ mod tests {
         let email = Email::parse("some@email.com").unwrap();
         let user = User {
             email: email.to_owned(),
-            password: Password::parse("password").unwrap(),
-            requires_2fa: true,
+            password: hash("password").await,
+            two_fa_method: TwoFAMethod::Email,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
         };
 
-        store.add_user(user.clone()).unwrap();
-        assert_eq!(user, store.get_user(email).unwrap());
+        store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user, store.get_user(email).await.unwrap());
         assert_eq!(
             UserStoreError::UserNotFound,
             store
                 .get_user(Email::parse("unknown@email.com").unwrap())
+                .await
                 .unwrap_err()
         );
     }
@@ -90,19 +135,111 @@ mod tests {
         let password = Password::parse("password").unwrap();
         let user = User {
             email: email.to_owned(),
-            password: password.to_owned(),
-            requires_2fa: true,
+            password: hash(password.as_ref()).await,
+            two_fa_method: TwoFAMethod::Email,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
         };
-        store.add_user(user.clone()).unwrap();
-        let result = store.validate_user(email.to_owned(), password.to_owned());
+        store.add_user(user.clone()).await.unwrap();
+        let result = store
+            .validate_user(email.to_owned(), password.to_owned())
+            .await;
         assert!(result.is_ok());
 
+        let error = store
+            .validate_user(email.to_owned(), Password::parse("wrong password").unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, UserStoreError::InvalidCredentials(_)));
+
         let error = store
             .validate_user(
                 Email::parse("invalid@email.com").unwrap(),
                 password.to_owned(),
             )
+            .await
             .unwrap_err();
         assert_eq!(error, UserStoreError::UserNotFound);
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("some@email.com").unwrap();
+        let user = User {
+            email: email.to_owned(),
+            password: hash("password").await,
+            two_fa_method: TwoFAMethod::None,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
+        };
+
+        assert_eq!(
+            store
+                .update_password(email.to_owned(), hash("new password").await)
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user).await.unwrap();
+        store
+            .update_password(email.to_owned(), hash("new password").await)
+            .await
+            .unwrap();
+
+        let result = store
+            .validate_user(email.to_owned(), Password::parse("new password").unwrap())
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_user() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("some@email.com").unwrap();
+        let mut user = User {
+            email: email.to_owned(),
+            password: hash("password").await,
+            two_fa_method: TwoFAMethod::None,
+            pending_totp_secret: None,
+            last_totp_step: None,
+            verified: false,
+        };
+
+        assert_eq!(
+            store.update_user(user.clone()).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user.clone()).await.unwrap();
+        user.two_fa_method = TwoFAMethod::Totp(TotpSecret::generate());
+        user.pending_totp_secret = Some(TotpSecret::generate());
+        user.last_totp_step = Some(56_666_667);
+        store.update_user(user.clone()).await.unwrap();
+
+        assert_eq!(store.get_user(email).await.unwrap(), user);
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("some@email.com").unwrap();
+        let user = User::new(email.to_owned(), hash("password").await, false);
+
+        assert_eq!(
+            store.delete_user(&email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user).await.unwrap();
+        store.delete_user(&email).await.unwrap();
+
+        assert_eq!(
+            store.get_user(email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
 }
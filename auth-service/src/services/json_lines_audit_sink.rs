@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use tokio::{
+    fs::OpenOptions,
+    io::{self, AsyncWrite, AsyncWriteExt},
+};
+
+use crate::domain::audit::{AuditEvent, AuditSink, AuditSinkError};
+
+/// Writes each event as a line of JSON. Without a file to append to, events
+/// are discarded.
+pub struct JsonLinesAuditSink {
+    writer: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+}
+
+impl JsonLinesAuditSink {
+    pub fn new(writer: impl AsyncWrite + Send + Sync + Unpin + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+        }
+    }
+
+    /// Appends to the file at `path`, which is created if need be. Events
+    /// already in there are left untouched.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+
+        Ok(Self::new(file))
+    }
+}
+
+impl Default for JsonLinesAuditSink {
+    fn default() -> Self {
+        Self::new(io::sink())
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for JsonLinesAuditSink {
+    async fn record(&mut self, event: &AuditEvent) -> Result<(), AuditSinkError> {
+        let mut line = serde_json::to_vec(event).map_err(|_| AuditSinkError::UnexpectedError)?;
+        line.push(b'\n');
+
+        // a single write per event, so that lines don't interleave
+        self.writer
+            .write_all(&line)
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+        self.writer
+            .flush()
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::domain::{audit::AuditEventKind, user::Email};
+
+    use super::*;
+
+    async fn read_events(path: &Path) -> Vec<AuditEvent> {
+        tokio::fs::read_to_string(path)
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_append_one_line_per_event() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut sink = JsonLinesAuditSink::open(&path).await.unwrap();
+
+        let signup = AuditEvent::new(AuditEventKind::Signup).email(&Email::default());
+        let logout =
+            AuditEvent::new(AuditEventKind::Logout { all_sessions: true }).email(&Email::default());
+        sink.record(&signup).await.unwrap();
+        sink.record(&logout).await.unwrap();
+
+        assert_eq!(read_events(&path).await, vec![signup, logout]);
+    }
+
+    #[tokio::test]
+    async fn test_keep_events_of_previous_runs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let first = AuditEvent::new(AuditEventKind::Signup);
+        let second = AuditEvent::new(AuditEventKind::LoginSucceeded);
+
+        let mut sink = JsonLinesAuditSink::open(&path).await.unwrap();
+        sink.record(&first).await.unwrap();
+        drop(sink);
+
+        let mut sink = JsonLinesAuditSink::open(&path).await.unwrap();
+        sink.record(&second).await.unwrap();
+
+        assert_eq!(read_events(&path).await, vec![first, second]);
+    }
+}
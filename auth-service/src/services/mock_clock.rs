@@ -0,0 +1,34 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::domain::clock::Clock;
+
+/// Clock frozen at a given instant, only moving when told to.
+pub struct MockClock {
+    now: DateTime<Utc>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now }
+    }
+
+    pub fn set(&mut self, now: DateTime<Utc>) {
+        self.now = now;
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+}
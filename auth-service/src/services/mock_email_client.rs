@@ -1,7 +1,17 @@
 use crate::domain::{user::Email, EmailClient, EmailClientResult};
 
+/// Logs emails instead of sending them. Their content holds codes and
+/// tokens: it's only logged, at debug level, when asked to for development.
 #[derive(Default)]
-pub struct MockEmailClient;
+pub struct MockEmailClient {
+    log_content: bool,
+}
+
+impl MockEmailClient {
+    pub fn new(log_content: bool) -> Self {
+        Self { log_content }
+    }
+}
 
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
@@ -11,12 +21,10 @@ impl EmailClient for MockEmailClient {
         subject: &str,
         content: &str,
     ) -> EmailClientResult<()> {
-        println!(
-            "Sending email to {} with subject: {} and content: {}",
-            recipient.as_ref(),
-            subject,
-            content
-        );
+        tracing::info!(recipient = recipient.as_ref(), subject, "Sending email");
+        if self.log_content {
+            tracing::debug!(recipient = recipient.as_ref(), content, "Email content");
+        }
 
         Ok(())
     }
@@ -1,5 +1,15 @@
 pub mod hashmap_banned_token_store;
+pub mod hashmap_failed_login_store;
+pub mod hashmap_one_time_token_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
 pub mod hashmap_two_fa_code_store;
 pub mod hashmap_user_store;
+pub mod json_lines_audit_sink;
+pub mod sqlite_recovery_code_store;
+pub mod sqlite_user_store;
+pub mod system_clock;
 
+pub mod mock_clock;
 pub mod mock_email_client;
@@ -0,0 +1,192 @@
+use sqlx::SqlitePool;
+
+use crate::domain::{
+    data_stores::recovery::{
+        HashedRecoveryCode, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError,
+    },
+    user::Email,
+};
+
+/// Keeps recovery codes in the database of the `SqliteUserStore`, whose
+/// migrations create the table.
+pub struct SqliteRecoveryCodeStore {
+    pool: SqlitePool,
+}
+
+impl SqliteRecoveryCodeStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_sqlx_error(error: sqlx::Error) -> RecoveryCodeStoreError {
+    tracing::error!(error = ?error, "Unexpected database error");
+    RecoveryCodeStoreError::UnexpectedError
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for SqliteRecoveryCodeStore {
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<HashedRecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let mut transaction = self.pool.begin().await.map_err(map_sqlx_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        for code in codes {
+            sqlx::query("INSERT OR IGNORE INTO recovery_codes (email, code_hash) VALUES (?, ?)")
+                .bind(email.as_ref())
+                .bind(code.as_ref())
+                .execute(&mut *transaction)
+                .await
+                .map_err(map_sqlx_error)?;
+        }
+
+        transaction.commit().await.map_err(map_sqlx_error)
+    }
+
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<usize, RecoveryCodeStoreError> {
+        let result = sqlx::query("DELETE FROM recovery_codes WHERE email = ? AND code_hash = ?")
+            .bind(email.as_ref())
+            .bind(code.hash().as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::InvalidCode);
+        }
+
+        self.remaining_codes(email).await
+    }
+
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE email = ?")
+            .bind(email.as_ref())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        usize::try_from(count).map_err(|_| RecoveryCodeStoreError::UnexpectedError)
+    }
+
+    async fn remove_codes(&mut self, email: &Email) -> Result<(), RecoveryCodeStoreError> {
+        sqlx::query("DELETE FROM recovery_codes WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::services::sqlite_user_store::SqliteUserStore;
+
+    use super::*;
+
+    async fn store_with_codes(
+        email: &Email,
+    ) -> (TempDir, SqliteRecoveryCodeStore, Vec<RecoveryCode>) {
+        let dir = TempDir::new().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("users.db").display());
+        let user_store = SqliteUserStore::connect(&url).await.unwrap();
+        let mut store = SqliteRecoveryCodeStore::new(user_store.pool());
+
+        let codes = RecoveryCode::generate_batch();
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+        store.set_codes(email, hashes).await.unwrap();
+
+        (dir, store, codes)
+    }
+
+    #[tokio::test]
+    async fn test_consume_code_only_once() {
+        let email = Email::default();
+        let (_dir, mut store, codes) = store_with_codes(&email).await;
+
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Ok(codes.len() - 1)
+        );
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+        assert_eq!(store.remaining_codes(&email).await, Ok(codes.len() - 1));
+    }
+
+    #[tokio::test]
+    async fn test_consume_unknown_code() {
+        let email = Email::default();
+        let (_dir, mut store, codes) = store_with_codes(&email).await;
+
+        assert_eq!(
+            store.consume_code(&email, &RecoveryCode::generate()).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+        assert_eq!(
+            store
+                .consume_code(&Email::parse("other@email.com").unwrap(), &codes[0])
+                .await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_codes_replaces_previous_ones() {
+        let email = Email::default();
+        let (_dir, mut store, old_codes) = store_with_codes(&email).await;
+
+        let new_code = RecoveryCode::generate();
+        store
+            .set_codes(&email, vec![new_code.hash()])
+            .await
+            .unwrap();
+
+        assert_eq!(store.remaining_codes(&email).await, Ok(1));
+        assert!(store.consume_code(&email, &old_codes[0]).await.is_err());
+        assert_eq!(store.consume_code(&email, &new_code).await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn test_remove_codes() {
+        let email = Email::default();
+        let (_dir, mut store, codes) = store_with_codes(&email).await;
+
+        store.remove_codes(&email).await.unwrap();
+
+        assert_eq!(store.remaining_codes(&email).await, Ok(0));
+        assert!(store.consume_code(&email, &codes[0]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_codes_survive_reconnection() {
+        let email = Email::default();
+        let (dir, store, codes) = store_with_codes(&email).await;
+        drop(store);
+
+        let url = format!("sqlite://{}", dir.path().join("users.db").display());
+        let user_store = SqliteUserStore::connect(&url).await.unwrap();
+        let mut store = SqliteRecoveryCodeStore::new(user_store.pool());
+
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Ok(codes.len() - 1)
+        );
+    }
+}
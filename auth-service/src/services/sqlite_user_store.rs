@@ -0,0 +1,403 @@
+use std::str::FromStr;
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+
+use crate::domain::{
+    data_stores::user::{UserStore, UserStoreError, UserStoreResult},
+    totp::TotpSecret,
+    user::{Email, HashedPassword, Password, TwoFAMethod, User},
+};
+
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Opens (creating it if needed) the database at `url` and brings its
+    /// schema up to date with the migrations embedded in the binary.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+        sqlx::migrate!().run(&pool).await?;
+
+        Ok(Self::new(pool))
+    }
+
+    /// Connection pool to the database, for the stores sharing it.
+    pub fn pool(&self) -> SqlitePool {
+        self.pool.clone()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    email: String,
+    password_hash: String,
+    two_fa_method: String,
+    totp_secret: Option<String>,
+    pending_totp_secret: Option<String>,
+    last_totp_step: Option<i64>,
+    verified: bool,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        let email = Email::parse(row.email)?;
+        let password = HashedPassword::parse_password_hash(row.password_hash).map_err(|e| {
+            tracing::error!(error = %e, "Invalid password hash found in store");
+            UserStoreError::UnexpectedError
+        })?;
+
+        let two_fa_method = match (row.two_fa_method.as_str(), row.totp_secret) {
+            ("none", _) => TwoFAMethod::None,
+            ("email", _) => TwoFAMethod::Email,
+            ("totp", Some(secret)) => TwoFAMethod::Totp(parse_totp_secret(secret)?),
+            (method, _) => {
+                tracing::error!(method, "Invalid 2FA method found in store");
+                return Err(UserStoreError::UnexpectedError);
+            }
+        };
+
+        let pending_totp_secret = row.pending_totp_secret.map(parse_totp_secret).transpose()?;
+        let last_totp_step = row
+            .last_totp_step
+            .map(u64::try_from)
+            .transpose()
+            .map_err(|e| {
+                tracing::error!(error = %e, "Invalid TOTP step found in store");
+                UserStoreError::UnexpectedError
+            })?;
+
+        Ok(User {
+            email,
+            password,
+            two_fa_method,
+            pending_totp_secret,
+            last_totp_step,
+            verified: row.verified,
+        })
+    }
+}
+
+fn parse_totp_secret(secret: String) -> UserStoreResult<TotpSecret> {
+    TotpSecret::parse(secret).map_err(|e| {
+        tracing::error!(error = %e, "Invalid TOTP secret found in store");
+        UserStoreError::UnexpectedError
+    })
+}
+
+/// Splits the 2FA method into the `two_fa_method` and `totp_secret` columns.
+fn two_fa_method_columns(method: &TwoFAMethod) -> (&'static str, Option<&str>) {
+    match method {
+        TwoFAMethod::None => ("none", None),
+        TwoFAMethod::Email => ("email", None),
+        TwoFAMethod::Totp(secret) => ("totp", Some(secret.as_ref())),
+    }
+}
+
+// Time steps are counted from the epoch, they fit in an `INTEGER` for ages.
+fn last_totp_step_column(user: &User) -> UserStoreResult<Option<i64>> {
+    user.last_totp_step
+        .map(i64::try_from)
+        .transpose()
+        .map_err(|_| UserStoreError::UnexpectedError)
+}
+
+fn map_sqlx_error(error: sqlx::Error) -> UserStoreError {
+    match error {
+        sqlx::Error::RowNotFound => UserStoreError::UserNotFound,
+        sqlx::Error::Database(e) if e.is_unique_violation() => UserStoreError::UserAlreadyExists,
+        e => {
+            tracing::error!(error = ?e, "Unexpected database error");
+            UserStoreError::UnexpectedError
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    async fn add_user(&mut self, user: User) -> UserStoreResult<()> {
+        let (two_fa_method, totp_secret) = two_fa_method_columns(&user.two_fa_method);
+
+        sqlx::query(
+            "INSERT INTO users (email, password_hash, two_fa_method, totp_secret, pending_totp_secret,
+                                last_totp_step, verified)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user.email.as_ref())
+        .bind(user.password.as_ref())
+        .bind(two_fa_method)
+        .bind(totp_secret)
+        .bind(user.pending_totp_secret.as_ref().map(AsRef::as_ref))
+        .bind(last_totp_step_column(&user)?)
+        .bind(user.verified)
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        Ok(())
+    }
+
+    async fn get_user(&self, email: Email) -> UserStoreResult<User> {
+        sqlx::query_as::<_, UserRow>(
+            "SELECT email, password_hash, two_fa_method, totp_secret, pending_totp_secret,
+                    last_totp_step, verified
+             FROM users WHERE email = ?",
+        )
+        .bind(email.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?
+        .try_into()
+    }
+
+    async fn validate_user(&self, email: Email, password: Password) -> UserStoreResult<()> {
+        let user = self.get_user(email).await?;
+        user.password.verify_raw_password(&password).await
+    }
+
+    async fn update_password(
+        &mut self,
+        email: Email,
+        password: HashedPassword,
+    ) -> UserStoreResult<()> {
+        let result = sqlx::query("UPDATE users SET password_hash = ? WHERE email = ?")
+            .bind(password.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn update_user(&mut self, user: User) -> UserStoreResult<()> {
+        let (two_fa_method, totp_secret) = two_fa_method_columns(&user.two_fa_method);
+
+        let result = sqlx::query(
+            "UPDATE users
+             SET password_hash = ?, two_fa_method = ?, totp_secret = ?, pending_totp_secret = ?,
+                 last_totp_step = ?, verified = ?
+             WHERE email = ?",
+        )
+        .bind(user.password.as_ref())
+        .bind(two_fa_method)
+        .bind(totp_secret)
+        .bind(user.pending_totp_secret.as_ref().map(AsRef::as_ref))
+        .bind(last_totp_step_column(&user)?)
+        .bind(user.verified)
+        .bind(user.email.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> UserStoreResult<()> {
+        let result = sqlx::query("DELETE FROM users WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::domain::user::PasswordHashingParams;
+
+    use super::*;
+
+    async fn hash(password: &str) -> HashedPassword {
+        HashedPassword::parse(
+            Password::parse(password).unwrap(),
+            PasswordHashingParams::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn temp_store() -> (TempDir, SqliteUserStore) {
+        let dir = TempDir::new().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("users.db").display());
+        let store = SqliteUserStore::connect(&url).await.unwrap();
+
+        (dir, store)
+    }
+
+    #[tokio::test]
+    async fn test_add_user() {
+        let (_dir, mut store) = temp_store().await;
+        let user = User::new(
+            Email::parse("some@email.com").unwrap(),
+            hash("password").await,
+            true,
+        );
+
+        assert!(store.add_user(user.clone()).await.is_ok());
+        assert_eq!(
+            store.add_user(user).await.unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user() {
+        let (_dir, mut store) = temp_store().await;
+        let email = Email::parse("some@email.com").unwrap();
+        let user = User::new(email.to_owned(), hash("password").await, true);
+
+        store.add_user(user.clone()).await.unwrap();
+        assert_eq!(user, store.get_user(email).await.unwrap());
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .get_user(Email::parse("unknown@email.com").unwrap())
+                .await
+                .unwrap_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_user() {
+        let (_dir, mut store) = temp_store().await;
+        let email = Email::parse("some@email.com").unwrap();
+        let password = Password::parse("password").unwrap();
+        let user = User::new(email.to_owned(), hash(password.as_ref()).await, false);
+        store.add_user(user).await.unwrap();
+
+        assert!(store
+            .validate_user(email.to_owned(), password.to_owned())
+            .await
+            .is_ok());
+        assert!(store
+            .validate_user(email.to_owned(), Password::parse("wrong password").unwrap())
+            .await
+            .is_err());
+        assert_eq!(
+            store
+                .validate_user(Email::parse("invalid@email.com").unwrap(), password)
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let (_dir, mut store) = temp_store().await;
+        let email = Email::parse("some@email.com").unwrap();
+        let user = User::new(email.to_owned(), hash("password").await, false);
+
+        assert_eq!(
+            store
+                .update_password(email.to_owned(), hash("new password").await)
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user).await.unwrap();
+        store
+            .update_password(email.to_owned(), hash("new password").await)
+            .await
+            .unwrap();
+
+        assert!(store
+            .validate_user(email, Password::parse("new password").unwrap())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_user() {
+        let (_dir, mut store) = temp_store().await;
+        let email = Email::parse("some@email.com").unwrap();
+        let mut user = User::new(email.to_owned(), hash("password").await, false);
+
+        assert_eq!(
+            store.update_user(user.clone()).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user.clone()).await.unwrap();
+        user.pending_totp_secret = Some(TotpSecret::generate());
+        store.update_user(user.clone()).await.unwrap();
+        assert_eq!(store.get_user(email.to_owned()).await.unwrap(), user);
+
+        user.two_fa_method = TwoFAMethod::Totp(user.pending_totp_secret.take().unwrap());
+        user.last_totp_step = Some(56_666_667);
+        store.update_user(user.clone()).await.unwrap();
+        assert_eq!(store.get_user(email.to_owned()).await.unwrap(), user);
+
+        user.verified = true;
+        store.update_user(user.clone()).await.unwrap();
+        assert_eq!(store.get_user(email).await.unwrap(), user);
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let (_dir, mut store) = temp_store().await;
+        let email = Email::parse("some@email.com").unwrap();
+        let user = User::new(email.to_owned(), hash("password").await, false);
+
+        assert_eq!(
+            store.delete_user(&email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        store.add_user(user.clone()).await.unwrap();
+        store.delete_user(&email).await.unwrap();
+        assert_eq!(
+            store.get_user(email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        // the email can be used again
+        assert!(store.add_user(user).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_data_survives_reconnection() {
+        let dir = TempDir::new().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("users.db").display());
+        let email = Email::parse("some@email.com").unwrap();
+
+        let mut store = SqliteUserStore::connect(&url).await.unwrap();
+        store
+            .add_user(User::new(email.to_owned(), hash("password").await, false))
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = SqliteUserStore::connect(&url).await.unwrap();
+        assert!(store.get_user(email).await.is_ok());
+    }
+}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+
+use crate::domain::clock::Clock;
+
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
@@ -1,26 +1,48 @@
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::Utc;
 use jsonwebtoken::{
-    decode, encode, errors::Error as JwtError, DecodingKey, EncodingKey, Validation,
+    decode, decode_header, encode, errors::Error as JwtError, Algorithm, Header, Validation,
 };
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{
-    app_state::BannedTokenStoreType,
-    domain::{data_stores::token::BannedTokenState, user::Email},
+    app_state::{BannedTokenStoreType, KeyringType},
+    domain::{
+        data_stores::{
+            refresh::{RefreshToken, REFRESH_TOKEN_TTL_SECONDS},
+            session::SessionId,
+            token::{BannedTokenState, BannedTokenStoreError},
+        },
+        user::Email,
+    },
 };
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    keys::JwtSigningKey,
+    settings::JwtSettings,
+};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum TokenValidationError {
     JwtError(JwtError),
+    UnknownKeyId,
+    InvalidSubject,
     BannedTokenError,
+    BannedTokenStoreError(BannedTokenStoreError),
 }
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token, for a session of the user. `two_fa`
+// tells whether the user went through a second factor to get it.
+pub fn generate_auth_cookie(
+    email: &Email,
+    session_id: &SessionId,
+    two_fa: bool,
+    signing_key: &JwtSigningKey,
+    jwt_settings: &JwtSettings,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(email, session_id, two_fa, signing_key, jwt_settings)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -35,6 +57,17 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Create cookie holding a refresh token. Unlike the JWT cookie, it outlives
+// the browser session, for as long as the token itself is valid.
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    Cookie::build((REFRESH_TOKEN_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        .build()
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -45,78 +78,161 @@ pub enum GenerateTokenError {
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+fn generate_auth_token(
+    email: &Email,
+    session_id: &SessionId,
+    two_fa: bool,
+    signing_key: &JwtSigningKey,
+    jwt_settings: &JwtSettings,
+) -> Result<String, GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
+    // Cast timestamps to usize, which is what Claims expects
     let exp: usize = exp
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let sub = email.as_ref().to_owned();
-
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        jti: Uuid::new_v4().to_string(),
+        iss: jwt_settings.issuer.clone(),
+        aud: jwt_settings.audience.clone(),
+        sid: session_id.as_ref().to_owned(),
+        two_fa,
+    };
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    create_token(&claims, signing_key).map_err(GenerateTokenError::TokenError)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Check if JWT auth token is valid by verifying its signature with the public
+// key it references (`kid` header), as long as that key wasn't retired. Only
+// EdDSA is accepted, whatever the header claims, and the token must have been
+// issued by us for the configured audience. There's no leeway on `exp`: bans
+// and retired keys are only kept until then.
 pub async fn validate_token(
     token: &str,
+    keyring: KeyringType,
+    jwt_settings: &JwtSettings,
     banned_token_store: BannedTokenStoreType,
 ) -> Result<Claims, TokenValidationError> {
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-    .map_err(TokenValidationError::JwtError)?;
-
-    if let BannedTokenState::Exists(email) = banned_token_store.read().await.verify(token) {
-        let decoded_email =
-            Email::parse(claims.sub.clone()).expect("Could not parse email address from claim");
+    let header = decode_header(token).map_err(TokenValidationError::JwtError)?;
+    let kid = header.kid.ok_or(TokenValidationError::UnknownKeyId)?;
+    let signing_key = keyring
+        .read()
+        .await
+        .find(&kid)
+        .ok_or(TokenValidationError::UnknownKeyId)?;
 
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_issuer(&[&jwt_settings.issuer]);
+    validation.set_audience(&[&jwt_settings.audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    validation.leeway = 0;
+
+    let claims = decode::<Claims>(token, signing_key.decoding_key(), &validation)
+        .map(|data| data.claims)
+        .map_err(TokenValidationError::JwtError)?;
+
+    let decoded_email =
+        Email::parse(claims.sub.clone()).map_err(|_| TokenValidationError::InvalidSubject)?;
+
+    let banned_token_state = banned_token_store
+        .read()
+        .await
+        .verify(&claims.jti)
+        .await
+        .map_err(TokenValidationError::BannedTokenStoreError)?;
+
+    if let BannedTokenState::Exists(email) = banned_token_state {
         if decoded_email == email {
             return Err(TokenValidationError::BannedTokenError);
         }
     }
 
+    // its session may have been revoked as a whole
+    let banned_session_state = banned_token_store
+        .read()
+        .await
+        .verify(&claims.sid)
+        .await
+        .map_err(TokenValidationError::BannedTokenStoreError)?;
+
+    if banned_session_state.email() == Some(decoded_email.clone()) {
+        return Err(TokenValidationError::BannedTokenError);
+    }
+
     Ok(claims)
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+// Create JWT auth token by signing the claims with our private key
+fn create_token(
+    claims: &Claims,
+    signing_key: &JwtSigningKey,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let header = Header {
+        kid: Some(signing_key.kid().to_owned()),
+        ..Header::new(Algorithm::EdDSA)
+    };
+
+    encode(&header, &claims, signing_key.encoding_key())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // equivalent to email address
     pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub jti: String, // unique id of the token, used to revoke it
+    pub iss: String,
+    pub aud: String,
+    pub sid: String, // session the token was issued for
+    #[serde(rename = "2fa")]
+    pub two_fa: bool, // whether a second factor was verified
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{services::hashmap_banned_token_store::HashmapBannedTokenStore, utils::ThreadSafe};
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use crate::{
+        domain::data_stores::token::{BannedTokenStore, BannedTokenStoreResult},
+        services::hashmap_banned_token_store::HashmapBannedTokenStore,
+        utils::{keys::Keyring, ThreadSafe},
+    };
 
     use super::*;
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(
+            &email,
+            &SessionId::default(),
+            false,
+            &JwtSigningKey::generate(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -135,20 +251,54 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::generate();
+        let cookie = create_refresh_cookie(&token);
+        assert_eq!(cookie.name(), REFRESH_TOKEN_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref());
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        );
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &JwtSigningKey::generate(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let result = validate_token(&token, HashmapBannedTokenStore::thread_safe())
-            .await
-            .unwrap();
+        let keyring = Keyring::thread_safe();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &keyring.read().await.active(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await
+        .unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -162,7 +312,365 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
-        let result = validate_token(&token, HashmapBannedTokenStore::thread_safe()).await;
+        let result = validate_token(
+            &token,
+            Keyring::thread_safe(),
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_other_key() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &JwtSigningKey::generate(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+        let result = validate_token(
+            &token,
+            Keyring::thread_safe(),
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+
+        assert_eq!(result.unwrap_err(), TokenValidationError::UnknownKeyId);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_rotated_key() {
+        let email = Email::parse("test@example.com").unwrap();
+        let keyring = Keyring::thread_safe();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &keyring.read().await.active(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+
+        keyring.write().await.rotate(
+            JwtSigningKey::generate(),
+            Utc::now() + chrono::Duration::minutes(10),
+        );
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key() {
+        let email = Email::parse("test@example.com").unwrap();
+        let keyring = Keyring::thread_safe();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &keyring.read().await.active(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+
+        keyring.write().await.rotate(
+            JwtSigningKey::generate(),
+            Utc::now() - chrono::Duration::seconds(1),
+        );
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), TokenValidationError::UnknownKeyId);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_other_algorithms() {
+        let keyring = Keyring::thread_safe();
+        let signing_key = keyring.read().await.active();
+        let claims = test_claims();
+        let header = Header {
+            kid: Some(signing_key.kid().to_owned()),
+            ..Header::new(Algorithm::HS256)
+        };
+        let token = encode(
+            &header,
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            TokenValidationError::JwtError(_)
+        ));
+    }
+
+    fn test_claims() -> Claims {
+        let now = Utc::now().timestamp() as usize;
+        let jwt_settings = JwtSettings::default();
+
+        Claims {
+            sub: "test@example.com".to_owned(),
+            exp: now + 600,
+            iat: now,
+            nbf: now,
+            jti: Uuid::new_v4().to_string(),
+            iss: jwt_settings.issuer,
+            aud: jwt_settings.audience,
+            sid: SessionId::default().as_ref().to_owned(),
+            two_fa: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_sets_registered_claims() {
+        let email = Email::parse("test@example.com").unwrap();
+        let keyring = Keyring::thread_safe();
+        let jwt_settings = JwtSettings::default();
+        let signing_key = keyring.read().await.active();
+
+        let first = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &signing_key,
+            &jwt_settings,
+        )
+        .unwrap();
+        let second = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &signing_key,
+            &jwt_settings,
+        )
+        .unwrap();
+
+        let banned_token_store = HashmapBannedTokenStore::thread_safe();
+        let first = validate_token(
+            &first,
+            keyring.clone(),
+            &jwt_settings,
+            banned_token_store.clone(),
+        )
+        .await
+        .unwrap();
+        let second = validate_token(&second, keyring, &jwt_settings, banned_token_store)
+            .await
+            .unwrap();
+
+        assert_eq!(first.iss, jwt_settings.issuer);
+        assert_eq!(first.aud, jwt_settings.audience);
+        assert!(first.iat <= Utc::now().timestamp() as usize);
+        assert_eq!(first.nbf, first.iat);
+        assert_ne!(first.jti, second.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_other_issuer_or_audience() {
+        let email = Email::parse("test@example.com").unwrap();
+        let keyring = Keyring::thread_safe();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &keyring.read().await.active(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+
+        let other_issuer = JwtSettings {
+            issuer: "someone-else".to_owned(),
+            ..JwtSettings::default()
+        };
+        let result = validate_token(
+            &token,
+            keyring.clone(),
+            &other_issuer,
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            TokenValidationError::JwtError(_)
+        ));
+
+        let other_audience = JwtSettings {
+            audience: "another-service".to_owned(),
+            ..JwtSettings::default()
+        };
+        let result = validate_token(
+            &token,
+            keyring,
+            &other_audience,
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            TokenValidationError::JwtError(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_token_not_yet_valid() {
+        let keyring = Keyring::thread_safe();
+        let signing_key = keyring.read().await.active();
+        let mut claims = test_claims();
+        claims.nbf += 300;
+        let token = create_token(&claims, &signing_key).unwrap();
+
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            TokenValidationError::JwtError(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_token_just_expired() {
+        let keyring = Keyring::thread_safe();
+        let signing_key = keyring.read().await.active();
+        let mut claims = test_claims();
+        // within the 60 seconds jsonwebtoken tolerates by default
+        claims.exp = claims.iat - 5;
+        claims.nbf = claims.iat - 600;
+        let token = create_token(&claims, &signing_key).unwrap();
+
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            HashmapBannedTokenStore::thread_safe(),
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            TokenValidationError::JwtError(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_banned_jti() {
+        let keyring = Keyring::thread_safe();
+        let signing_key = keyring.read().await.active();
+        let claims = test_claims();
+        let token = create_token(&claims, &signing_key).unwrap();
+
+        let banned_token_store = HashmapBannedTokenStore::thread_safe();
+        banned_token_store
+            .write()
+            .await
+            .add(
+                &Email::parse(claims.sub.clone()).unwrap(),
+                &claims.jti,
+                claims.exp,
+            )
+            .await
+            .unwrap();
+
+        let result =
+            validate_token(&token, keyring, &JwtSettings::default(), banned_token_store).await;
+        assert_eq!(result.unwrap_err(), TokenValidationError::BannedTokenError);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_banned_session() {
+        let keyring = Keyring::thread_safe();
+        let signing_key = keyring.read().await.active();
+        let claims = test_claims();
+        let token = create_token(&claims, &signing_key).unwrap();
+
+        let banned_token_store = HashmapBannedTokenStore::thread_safe();
+        banned_token_store
+            .write()
+            .await
+            .add(
+                &Email::parse(claims.sub.clone()).unwrap(),
+                &claims.sid,
+                claims.exp,
+            )
+            .await
+            .unwrap();
+
+        let result =
+            validate_token(&token, keyring, &JwtSettings::default(), banned_token_store).await;
+        assert_eq!(result.unwrap_err(), TokenValidationError::BannedTokenError);
+    }
+
+    struct FailingBannedTokenStore;
+
+    #[async_trait::async_trait]
+    impl BannedTokenStore for FailingBannedTokenStore {
+        async fn add(
+            &mut self,
+            _email: &Email,
+            _jti: &str,
+            _expires_at: usize,
+        ) -> BannedTokenStoreResult<()> {
+            Err(BannedTokenStoreError::UnexpectedError)
+        }
+
+        async fn verify(&self, _jti: &str) -> BannedTokenStoreResult<BannedTokenState> {
+            Err(BannedTokenStoreError::UnexpectedError)
+        }
+
+        async fn len(&self) -> BannedTokenStoreResult<usize> {
+            Err(BannedTokenStoreError::UnexpectedError)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_propagates_banned_token_store_errors() {
+        let email = Email::parse("test@example.com").unwrap();
+        let keyring = Keyring::thread_safe();
+        let token = generate_auth_token(
+            &email,
+            &SessionId::default(),
+            false,
+            &keyring.read().await.active(),
+            &JwtSettings::default(),
+        )
+        .unwrap();
+        let result = validate_token(
+            &token,
+            keyring,
+            &JwtSettings::default(),
+            Arc::new(RwLock::new(FailingBannedTokenStore)),
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            TokenValidationError::BannedTokenStoreError(BannedTokenStoreError::UnexpectedError)
+        );
+    }
 }
@@ -1,28 +1,35 @@
-use std::env as std_env;
-
-use lazy_static::lazy_static;
-
-lazy_static! {
-    pub static ref JWT_SECRET: String = set_token();
-}
-
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
+pub const DEFAULT_TOTP_ISSUER: &str = "Auth Service";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
 
 pub mod env {
-    pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
-}
-
-fn set_token() -> String {
-    dotenvy::dotenv().ok();
-    let secret = std_env::var(env::JWT_SECRET_ENV_VAR).expect("JWT_SECRET must be set.");
-    if secret.is_empty() {
-        panic!("JWT_SECRET must not be empty.");
-    }
-    secret
+    pub const JWT_SIGNING_KEY_PATH_ENV_VAR: &str = "JWT_SIGNING_KEY_PATH";
+    pub const JWT_KEYRING_PATH_ENV_VAR: &str = "JWT_KEYRING_PATH";
+    pub const ALLOW_EPHEMERAL_SIGNING_KEY_ENV_VAR: &str = "ALLOW_EPHEMERAL_SIGNING_KEY";
+    pub const LOG_EMAIL_CONTENT_ENV_VAR: &str = "LOG_EMAIL_CONTENT";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const PASSWORD_HASH_MEMORY_COST_ENV_VAR: &str = "PASSWORD_HASH_MEMORY_COST";
+    pub const PASSWORD_HASH_ITERATIONS_ENV_VAR: &str = "PASSWORD_HASH_ITERATIONS";
+    pub const PASSWORD_HASH_PARALLELISM_ENV_VAR: &str = "PASSWORD_HASH_PARALLELISM";
+    pub const TWO_FA_CODE_LENGTH_ENV_VAR: &str = "TWO_FA_CODE_LENGTH";
+    pub const USER_STORE_ENV_VAR: &str = "USER_STORE";
+    pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const TOTP_ISSUER_ENV_VAR: &str = "TOTP_ISSUER";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const INTROSPECTION_API_KEY_ENV_VAR: &str = "INTROSPECTION_API_KEY";
+    pub const REQUIRE_VERIFIED_EMAIL_ENV_VAR: &str = "REQUIRE_VERIFIED_EMAIL";
+    pub const TRUSTED_PROXY_HOPS_ENV_VAR: &str = "TRUSTED_PROXY_HOPS";
+    pub const RATE_LIMITS_ENV_VAR: &str = "RATE_LIMITS";
+    pub const AUDIT_LOG_PATH_ENV_VAR: &str = "AUDIT_LOG_PATH";
+    pub const LOG_FORMAT_ENV_VAR: &str = "LOG_FORMAT";
 }
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
+    pub const DATABASE_URL: &str = "sqlite://auth-service.db";
 }
 
 pub mod test {
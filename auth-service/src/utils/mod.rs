@@ -4,6 +4,10 @@ use tokio::sync::RwLock;
 
 pub mod auth;
 pub mod constants;
+pub mod keys;
+pub mod redact;
+pub mod settings;
+pub mod telemetry;
 
 /// Objects that use the Default trait will be able to initialize
 /// a "thread-safe" of themselves, wrapping the default instance into
@@ -0,0 +1,18 @@
+/// Shown in place of secrets wherever they could end up in logs.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Implements `Debug` for types holding secrets (passwords, codes, tokens...)
+/// so that only their name is shown, never what's inside.
+macro_rules! redacted_debug {
+    ($($type:ty),+ $(,)?) => {
+        $(
+            impl std::fmt::Debug for $type {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    write!(f, "{}({})", stringify!($type), $crate::utils::redact::REDACTED)
+                }
+            }
+        )+
+    };
+}
+
+pub(crate) use redacted_debug;
@@ -0,0 +1,305 @@
+use std::{collections::HashMap, env as std_env, str::FromStr};
+
+use crate::domain::{data_stores::twofa::DEFAULT_TWO_FA_CODE_LENGTH, user::PasswordHashingParams};
+
+use super::constants::{env, prod, DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER, DEFAULT_TOTP_ISSUER};
+
+/// Runtime configuration of the service. `Default` gives sensible values for
+/// development and tests, `from_env` lets each of them be overridden.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppSettings {
+    pub password_hashing: PasswordHashingParams,
+    pub user_store: UserStoreBackend,
+    pub two_fa_code_length: usize,
+    /// Name shown next to the account in authenticator apps
+    pub totp_issuer: String,
+    /// PEM file holding the Ed25519 private key signing our JWTs
+    pub jwt_signing_key_path: Option<String>,
+    /// File the signing keys are saved to on rotation. When it exists, it's
+    /// loaded on startup instead of `jwt_signing_key_path`.
+    pub jwt_keyring_path: Option<String>,
+    /// Whether a throwaway signing key may be generated when no path is set,
+    /// for development only: tokens don't survive a restart then
+    pub allow_ephemeral_signing_key: bool,
+    /// Whether the content of emails, codes and tokens included, is logged at
+    /// debug level. No email is delivered otherwise: for development only.
+    pub log_email_content: bool,
+    pub jwt: JwtSettings,
+    /// Bearer token expected by the admin routes, which are disabled without it
+    pub admin_api_key: Option<String>,
+    /// Bearer token expected from services introspecting tokens, which can't
+    /// without it
+    pub introspection_api_key: Option<String>,
+    /// Whether users must verify their email address before logging in
+    pub require_verified_email: bool,
+    /// Number of proxies in front of the service, each appending the address
+    /// it got the request from to `X-Forwarded-For`
+    pub trusted_proxy_hops: usize,
+    /// Requests allowed per client address on each route, keyed by the path
+    /// the route is registered with (eg: `/sessions/:id`). Routes not listed
+    /// aren't limited.
+    pub rate_limits: HashMap<String, RateLimit>,
+    /// File the audit log is appended to, as JSON lines. Without one, audit
+    /// events are discarded.
+    pub audit_log_path: Option<String>,
+    pub log_format: LogFormat,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            password_hashing: PasswordHashingParams::default(),
+            user_store: UserStoreBackend::default(),
+            two_fa_code_length: DEFAULT_TWO_FA_CODE_LENGTH,
+            totp_issuer: DEFAULT_TOTP_ISSUER.to_owned(),
+            jwt_signing_key_path: None,
+            jwt_keyring_path: None,
+            allow_ephemeral_signing_key: false,
+            log_email_content: false,
+            jwt: JwtSettings::default(),
+            admin_api_key: None,
+            introspection_api_key: None,
+            require_verified_email: false,
+            trusted_proxy_hops: 0,
+            rate_limits: default_rate_limits(),
+            audit_log_path: None,
+            log_format: LogFormat::default(),
+        }
+    }
+}
+
+/// At most `requests` per `period_seconds`, which may all be made at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, period_seconds: u64) -> Self {
+        Self {
+            requests,
+            period_seconds,
+        }
+    }
+}
+
+/// Parses `<requests>/<seconds>`, eg: `10/60` for 10 requests per minute.
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, period_seconds) = s
+            .split_once('/')
+            .ok_or_else(|| format!("Expected <requests>/<seconds>. Got: \"{s}\""))?;
+        let requests: u32 = requests
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid number of requests. Details: {e:?}"))?;
+        let period_seconds: u64 = period_seconds
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid period. Details: {e:?}"))?;
+
+        if requests == 0 || period_seconds == 0 {
+            return Err(format!("Both numbers must be positive. Got: \"{s}\""));
+        }
+
+        Ok(Self::new(requests, period_seconds))
+    }
+}
+
+// Routes that can be abused to guess secrets, spam users or hammer our
+// password hashing.
+fn default_rate_limits() -> HashMap<String, RateLimit> {
+    [
+        ("/signup", RateLimit::new(20, 60)),
+        ("/login", RateLimit::new(30, 60)),
+        ("/verify-2fa", RateLimit::new(20, 60)),
+        ("/verify-email", RateLimit::new(20, 60)),
+        ("/verify-email/resend", RateLimit::new(5, 60)),
+        ("/password-reset/request", RateLimit::new(5, 60)),
+        ("/password-reset/confirm", RateLimit::new(20, 60)),
+        ("/verify-token", RateLimit::new(600, 60)),
+    ]
+    .into_iter()
+    .map(|(route, limit)| (route.to_owned(), limit))
+    .collect()
+}
+
+/// Claims identifying who issued our JWTs and who they are meant for. Tokens
+/// not matching both are rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtSettings {
+    /// `iss` claim
+    pub issuer: String,
+    /// `aud` claim
+    pub audience: String,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            issuer: DEFAULT_JWT_ISSUER.to_owned(),
+            audience: DEFAULT_JWT_AUDIENCE.to_owned(),
+        }
+    }
+}
+
+/// How logs are written to the standard output.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human readable, for development
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            other => Err(format!("Expected \"pretty\" or \"json\". Got: \"{other}\"")),
+        }
+    }
+}
+
+/// Where user accounts are persisted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum UserStoreBackend {
+    #[default]
+    InMemory,
+    Sqlite {
+        url: String,
+    },
+}
+
+impl UserStoreBackend {
+    fn from_env() -> Self {
+        let backend = std_env::var(env::USER_STORE_ENV_VAR).unwrap_or_default();
+
+        match backend.as_str() {
+            "" | "memory" => Self::InMemory,
+            "sqlite" => Self::Sqlite {
+                url: std_env::var(env::DATABASE_URL_ENV_VAR)
+                    .unwrap_or_else(|_| prod::DATABASE_URL.to_owned()),
+            },
+            other => panic!(
+                "{} has an invalid value: \"{other}\" (expected \"memory\" or \"sqlite\")",
+                env::USER_STORE_ENV_VAR
+            ),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+        let defaults = Self::default();
+
+        let password_hashing = PasswordHashingParams {
+            memory_cost: parse_env_var(
+                env::PASSWORD_HASH_MEMORY_COST_ENV_VAR,
+                defaults.password_hashing.memory_cost,
+            ),
+            iterations: parse_env_var(
+                env::PASSWORD_HASH_ITERATIONS_ENV_VAR,
+                defaults.password_hashing.iterations,
+            ),
+            parallelism: parse_env_var(
+                env::PASSWORD_HASH_PARALLELISM_ENV_VAR,
+                defaults.password_hashing.parallelism,
+            ),
+        };
+
+        Self {
+            password_hashing,
+            user_store: UserStoreBackend::from_env(),
+            two_fa_code_length: parse_env_var(
+                env::TWO_FA_CODE_LENGTH_ENV_VAR,
+                defaults.two_fa_code_length,
+            ),
+            totp_issuer: parse_env_var(env::TOTP_ISSUER_ENV_VAR, defaults.totp_issuer),
+            jwt_signing_key_path: std_env::var(env::JWT_SIGNING_KEY_PATH_ENV_VAR).ok(),
+            jwt_keyring_path: std_env::var(env::JWT_KEYRING_PATH_ENV_VAR).ok(),
+            allow_ephemeral_signing_key: parse_env_var(
+                env::ALLOW_EPHEMERAL_SIGNING_KEY_ENV_VAR,
+                defaults.allow_ephemeral_signing_key,
+            ),
+            log_email_content: parse_env_var(
+                env::LOG_EMAIL_CONTENT_ENV_VAR,
+                defaults.log_email_content,
+            ),
+            jwt: JwtSettings {
+                issuer: parse_env_var(env::JWT_ISSUER_ENV_VAR, defaults.jwt.issuer),
+                audience: parse_env_var(env::JWT_AUDIENCE_ENV_VAR, defaults.jwt.audience),
+            },
+            admin_api_key: parse_api_key_env_var(env::ADMIN_API_KEY_ENV_VAR),
+            introspection_api_key: parse_api_key_env_var(env::INTROSPECTION_API_KEY_ENV_VAR),
+            require_verified_email: parse_env_var(
+                env::REQUIRE_VERIFIED_EMAIL_ENV_VAR,
+                defaults.require_verified_email,
+            ),
+            trusted_proxy_hops: parse_env_var(
+                env::TRUSTED_PROXY_HOPS_ENV_VAR,
+                defaults.trusted_proxy_hops,
+            ),
+            rate_limits: parse_rate_limits_env_var(env::RATE_LIMITS_ENV_VAR, defaults.rate_limits),
+            audit_log_path: std_env::var(env::AUDIT_LOG_PATH_ENV_VAR).ok(),
+            log_format: parse_env_var(env::LOG_FORMAT_ENV_VAR, defaults.log_format),
+        }
+    }
+}
+
+fn parse_env_var<T: FromStr>(name: &str, default: T) -> T {
+    match std_env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} has an invalid value: \"{value}\"")),
+        Err(_) => default,
+    }
+}
+
+// An empty key would let anyone in: it's treated as no key at all.
+fn parse_api_key_env_var(name: &str) -> Option<String> {
+    std_env::var(name).ok().filter(|key| !key.is_empty())
+}
+
+// Comma separated `<route>=<requests>/<seconds>`, eg: `/login=10/60,/signup=5/60`.
+// Listed routes override their default limit, the others keep it.
+fn parse_rate_limits_env_var(
+    name: &str,
+    mut rate_limits: HashMap<String, RateLimit>,
+) -> HashMap<String, RateLimit> {
+    let Ok(value) = std_env::var(name) else {
+        return rate_limits;
+    };
+
+    for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let (route, limit) = entry
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{name} has an invalid entry: \"{entry}\""));
+        let limit = limit
+            .parse()
+            .unwrap_or_else(|e| panic!("{name} has an invalid limit for {route}: {e}"));
+        rate_limits.insert(route.trim().to_owned(), limit);
+    }
+
+    rate_limits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_log_formats() {
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use axum::{body::Body, http::Request};
+use tracing::Span;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use super::settings::LogFormat;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Sends logs to the standard output, in the given format. Only those of
+/// level `info` and above are kept, unless `RUST_LOG` says otherwise.
+pub fn init_tracing(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match format {
+        LogFormat::Pretty => registry.with(fmt::layer().pretty()).init(),
+        LogFormat::Json => registry
+            .with(fmt::layer().json().with_span_list(false))
+            .init(),
+    }
+}
+
+/// Span wrapping everything logged while handling a request, tagged with the
+/// ID it was given. Only the path is recorded: neither the query string nor
+/// the headers, which may hold tokens.
+pub fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    )
+}
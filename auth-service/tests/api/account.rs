@@ -0,0 +1,277 @@
+use auth_service::{
+    domain::{
+        data_stores::{
+            failed_login::MAX_FAILED_LOGINS_PER_ACCOUNT, one_time::OneTimeTokenPurpose,
+            twofa::TwoFACodeStoreError, user::UserStoreError,
+        },
+        user::Email,
+    },
+    routes::TwoFactorAuthResponse,
+};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, Device, ResponseExt, TestApp};
+
+// Signs up a user and logs them in from the app's client.
+async fn log_in(app: &TestApp) -> Email {
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    Email::parse(email).unwrap()
+}
+
+// Signs up a user with 2FA and logs them in from the app's client.
+async fn log_in_with_2fa(app: &TestApp) -> Email {
+    let email = Email::parse(get_random_email()).unwrap();
+    let body = json!({"email": email.as_ref(), "password": "password", "requires2FA": true});
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email.as_ref(),
+            "loginAttemptId": login_attempt_id,
+            "2FACode": get_two_fa_code(app, &email).await,
+        }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    email
+}
+
+async fn get_two_fa_code(app: &TestApp, email: &Email) -> String {
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(email)
+        .await
+        .unwrap();
+
+    code.as_ref().to_owned()
+}
+
+// Checks that nothing is left about the user, but the bans of their tokens.
+async fn assert_purged(app: &TestApp, email: &Email) {
+    assert_eq!(
+        app.user_store.read().await.get_user(email.to_owned()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert!(app
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        app.two_fa_code_store.read().await.get_code(email).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(
+        app.recovery_code_store
+            .read()
+            .await
+            .remaining_codes(email)
+            .await,
+        Ok(0)
+    );
+    for purpose in [
+        OneTimeTokenPurpose::PasswordReset,
+        OneTimeTokenPurpose::EmailVerification,
+    ] {
+        assert_eq!(
+            app.one_time_token_store
+                .read()
+                .await
+                .last_issued_at(email, purpose)
+                .await,
+            Ok(None)
+        );
+    }
+    // the bans of the user's tokens are kept until they expire
+    assert!(!app
+        .banned_token_store
+        .read()
+        .await
+        .is_empty()
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.delete_account(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    log_in(&app).await;
+
+    let response = app.delete_account(&json!({})).await;
+    assert_eq!(response.status_code(), 422);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    let response = app
+        .delete_account(&json!({"password": "wrong-password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    assert!(app.user_store.read().await.get_user(email).await.is_ok());
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_too_many_incorrect_passwords() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_ACCOUNT {
+        let response = app
+            .delete_account(&json!({"password": "wrong-password"}))
+            .await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app.delete_account(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 429);
+
+    assert!(app.user_store.read().await.get_user(email).await.is_ok());
+}
+
+#[tokio::test]
+async fn should_delete_the_account() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    let other_device = Device::log_in(&app, &body).await;
+    app.post_login(&body).await;
+    let sent_before = app.sent_emails.to(&email).len();
+    app.post_password_reset_request(&json!({"email": email}))
+        .await;
+    app.sent_emails
+        .wait_for_next(&email, sent_before)
+        .await
+        .expect("No password reset token was emailed");
+
+    let response = app.delete_account(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 200);
+
+    let auth_cookie = response.get_auth_cookie().unwrap();
+    assert!(auth_cookie.value().is_empty());
+    let refresh_cookie = response.get_refresh_cookie().unwrap();
+    assert!(refresh_cookie.value().is_empty());
+
+    assert_purged(&app, &Email::parse(email.clone()).unwrap()).await;
+
+    // the tokens issued before are revoked
+    let response = app
+        .post_verify_token(&json!({"token": other_device.token}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = other_device.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_require_a_2fa_code_if_enabled() {
+    let app = TestApp::new().await;
+    let email = log_in_with_2fa(&app).await;
+
+    let response = app.delete_account(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+    assert!(app
+        .user_store
+        .read()
+        .await
+        .get_user(email.clone())
+        .await
+        .is_ok());
+
+    let response = app
+        .delete_account(&json!({
+            "password": "password",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": get_two_fa_code(&app, &email).await,
+        }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    assert_purged(&app, &email).await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_2fa_code() {
+    let app = TestApp::new().await;
+    let email = log_in_with_2fa(&app).await;
+
+    let response = app.delete_account(&json!({"password": "password"})).await;
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    let code = get_two_fa_code(&app, &email).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    let response = app
+        .delete_account(&json!({
+            "password": "password",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code,
+        }))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    assert!(app.user_store.read().await.get_user(email).await.is_ok());
+}
+
+#[tokio::test]
+async fn should_let_the_email_sign_up_again() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    app.delete_account(&json!({"password": "password"})).await;
+
+    let body = json!({"email": email.as_ref(), "password": "new-password", "requires2FA": false});
+    let response = app.post_signup(&body).await;
+    assert_eq!(response.status_code(), 201);
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    // the bans left by the deletion don't apply to the new account
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+    let response = app.post_verify_token(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 200);
+}
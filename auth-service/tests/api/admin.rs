@@ -0,0 +1,121 @@
+use auth_service::{
+    routes::RotateSigningKeyResponse,
+    utils::{keys::Keyring, settings::AppSettings},
+};
+use jsonwebtoken::jwk::JwkSet;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+const ADMIN_API_KEY: &str = "admin-api-key";
+
+async fn app_with_admin_api_key() -> TestApp {
+    app_with_settings(AppSettings::default()).await
+}
+
+async fn app_with_settings(settings: AppSettings) -> TestApp {
+    TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            admin_api_key: Some(ADMIN_API_KEY.to_owned()),
+            ..settings
+        })
+    })
+    .await
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_api_key_is_missing() {
+    let app = app_with_admin_api_key().await;
+
+    let response = app.post_rotate_signing_key(None).await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_admin_api_key_is_incorrect() {
+    let app = app_with_admin_api_key().await;
+    let kid = app.keyring.read().await.active().kid().to_owned();
+
+    let response = app.post_rotate_signing_key(Some("wrong-key")).await;
+    assert_eq!(response.status_code(), 401);
+    assert_eq!(app.keyring.read().await.active().kid(), kid);
+}
+
+#[tokio::test]
+async fn should_return_401_if_no_admin_api_key_is_configured() {
+    let app = TestApp::new().await;
+
+    let response = app.post_rotate_signing_key(Some(ADMIN_API_KEY)).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_rotate_signing_key_without_invalidating_issued_tokens() {
+    let app = app_with_admin_api_key().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    let old_token = response.get_auth_cookie().unwrap().value().to_owned();
+    let old_kid = app.keyring.read().await.active().kid().to_owned();
+
+    let response = app.post_rotate_signing_key(Some(ADMIN_API_KEY)).await;
+    assert_eq!(response.status_code(), 200);
+
+    let rotation = response.json::<RotateSigningKeyResponse>().await.unwrap();
+    assert_eq!(rotation.previous_kid, old_kid);
+    assert_ne!(rotation.kid, old_kid);
+    assert_eq!(app.keyring.read().await.active().kid(), rotation.kid);
+
+    // both keys are published while tokens signed with the old one are live
+    let jwks = app.get_jwks().await.json::<JwkSet>().await.unwrap();
+    assert!(jwks.find(&old_kid).is_some());
+    assert!(jwks.find(&rotation.kid).is_some());
+
+    let response = app.post_verify_token(&json!({"token": old_token})).await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app.post_login(&body).await;
+    let new_token = response.get_auth_cookie().unwrap().value().to_owned();
+    let header = jsonwebtoken::decode_header(&new_token).unwrap();
+    assert_eq!(header.kid, Some(rotation.kid));
+
+    let response = app.post_verify_token(&json!({"token": new_token})).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_refuse_to_rotate_a_configured_key_without_keyring_file() {
+    let app = app_with_settings(AppSettings {
+        jwt_signing_key_path: Some("jwt_signing_key.pem".to_owned()),
+        ..AppSettings::default()
+    })
+    .await;
+    let kid = app.keyring.read().await.active().kid().to_owned();
+
+    let response = app.post_rotate_signing_key(Some(ADMIN_API_KEY)).await;
+    assert_eq!(response.status_code(), 400);
+    assert_eq!(app.keyring.read().await.active().kid(), kid);
+}
+
+#[tokio::test]
+async fn should_save_rotated_keys_to_keyring_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let keyring_path = dir.path().join("keyring.pem").display().to_string();
+    let app = app_with_settings(AppSettings {
+        jwt_signing_key_path: Some("jwt_signing_key.pem".to_owned()),
+        jwt_keyring_path: Some(keyring_path.clone()),
+        ..AppSettings::default()
+    })
+    .await;
+
+    let response = app.post_rotate_signing_key(Some(ADMIN_API_KEY)).await;
+    assert_eq!(response.status_code(), 200);
+    let rotation = response.json::<RotateSigningKeyResponse>().await.unwrap();
+
+    // what a restart would load
+    let saved = Keyring::load(&keyring_path).unwrap().unwrap();
+    assert_eq!(saved.active().kid(), rotation.kid);
+    assert!(saved.find(&rotation.previous_kid).is_some());
+}
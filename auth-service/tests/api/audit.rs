@@ -0,0 +1,189 @@
+use auth_service::{
+    domain::{
+        audit::{AuditEventKind, LoginFailure},
+        user::Email,
+    },
+    routes::TwoFactorAuthResponse,
+    utils::settings::AppSettings,
+};
+use reqwest::header::USER_AGENT;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+fn signup_body(requires_2fa: bool) -> serde_json::Value {
+    json!({"email": get_random_email(), "password": "password", "requires2FA": requires_2fa})
+}
+
+fn login_body(signup_body: &serde_json::Value) -> serde_json::Value {
+    json!({"email": signup_body["email"], "password": signup_body["password"]})
+}
+
+fn email_of(body: &serde_json::Value) -> &str {
+    body["email"].as_str().unwrap()
+}
+
+#[tokio::test]
+async fn should_record_who_did_what_from_where() {
+    let app = TestApp::new().await;
+    let body = signup_body(false);
+
+    let response = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .header(USER_AGENT, "audit-test/1.0")
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status_code(), 201);
+
+    let event = app.audit_events.last().expect("No event recorded");
+    assert_eq!(event.kind, AuditEventKind::Signup);
+    assert_eq!(event.email.as_deref(), Some(email_of(&body)));
+    assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(event.user_agent.as_deref(), Some("audit-test/1.0"));
+}
+
+#[tokio::test]
+async fn should_record_logins_and_logouts() {
+    let app = TestApp::new().await;
+    let body = signup_body(false);
+    app.post_signup(&body).await;
+
+    assert_eq!(app.post_login(&login_body(&body)).await.status_code(), 200);
+    assert_eq!(app.post_logout().await.status_code(), 200);
+    assert_eq!(app.post_login(&login_body(&body)).await.status_code(), 200);
+    assert_eq!(app.post_logout_all().await.status_code(), 200);
+
+    assert_eq!(
+        app.audit_events.kinds_for(email_of(&body)),
+        vec![
+            AuditEventKind::Signup,
+            AuditEventKind::LoginSucceeded,
+            AuditEventKind::Logout {
+                all_sessions: false
+            },
+            AuditEventKind::LoginSucceeded,
+            AuditEventKind::Logout { all_sessions: true },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_record_why_logins_failed() {
+    let app = TestApp::new().await;
+    let body = signup_body(false);
+    app.post_signup(&body).await;
+
+    let wrong_password = json!({"email": body["email"], "password": "wrong-password"});
+    let response = app.post_login(&wrong_password).await;
+    assert_eq!(response.status_code(), 401);
+
+    let unknown_email = get_random_email();
+    let response = app
+        .post_login(&json!({"email": unknown_email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    assert_eq!(
+        app.audit_events.kinds_for(email_of(&body)).last(),
+        Some(&AuditEventKind::LoginFailed {
+            reason: LoginFailure::WrongPassword
+        })
+    );
+    assert_eq!(
+        app.audit_events.kinds_for(&unknown_email),
+        vec![AuditEventKind::LoginFailed {
+            reason: LoginFailure::UnknownUser
+        }]
+    );
+}
+
+#[tokio::test]
+async fn should_record_logins_of_unverified_users() {
+    let app = TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            require_verified_email: true,
+            ..AppSettings::default()
+        })
+    })
+    .await;
+    let body = signup_body(false);
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&login_body(&body)).await;
+    assert_eq!(response.status_code(), 403);
+
+    assert_eq!(
+        app.audit_events.kinds_for(email_of(&body)).last(),
+        Some(&AuditEventKind::LoginFailed {
+            reason: LoginFailure::EmailNotVerified
+        })
+    );
+}
+
+#[tokio::test]
+async fn should_record_the_second_factor() {
+    let app = TestApp::new().await;
+    let body = signup_body(true);
+    app.post_signup(&body).await;
+    let email = email_of(&body);
+
+    let response = app.post_login(&login_body(&body)).await;
+    assert_eq!(response.status_code(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    let wrong_code = if code.as_ref() == "000000" {
+        "111111"
+    } else {
+        "000000"
+    };
+
+    let verify_body =
+        |code: &str| json!({"email": email, "loginAttemptId": login_attempt_id, "2FACode": code});
+    let response = app.post_verify_2fa(&verify_body(wrong_code)).await;
+    assert_eq!(response.status_code(), 401);
+    let response = app.post_verify_2fa(&verify_body(code.as_ref())).await;
+    assert_eq!(response.status_code(), 200);
+
+    assert_eq!(
+        app.audit_events.kinds_for(email),
+        vec![
+            AuditEventKind::Signup,
+            AuditEventKind::TwoFACodeSent,
+            AuditEventKind::TwoFAFailed,
+            AuditEventKind::TwoFAVerified,
+            AuditEventKind::LoginSucceeded,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_record_rejected_tokens() {
+    let app = TestApp::new().await;
+    let body = signup_body(false);
+    app.post_signup(&body).await;
+    let response = app.post_login(&login_body(&body)).await;
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+
+    // a token banned on logout, presented again
+    app.post_logout().await;
+    let response = app.post_verify_token(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 401);
+
+    let event = app.audit_events.last().expect("No event recorded");
+    assert_eq!(event.kind, AuditEventKind::TokenRejected);
+    assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+}
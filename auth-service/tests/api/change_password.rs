@@ -0,0 +1,171 @@
+use auth_service::domain::data_stores::failed_login::MAX_FAILED_LOGINS_PER_ACCOUNT;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, Device, ResponseExt, TestApp};
+
+// Signs up a user and logs them in from the app's client.
+async fn log_in(app: &TestApp) -> String {
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    email
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_change_password(
+            &json!({"currentPassword": "password", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    log_in(&app).await;
+
+    let response = app
+        .post_change_password(&json!({"newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 422);
+}
+
+#[tokio::test]
+async fn should_change_the_password() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    let response = app
+        .post_change_password(
+            &json!({"currentPassword": "password", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_notify_the_user_by_email() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    app.post_change_password(
+        &json!({"currentPassword": "password", "newPassword": "new-password"}),
+    )
+    .await;
+
+    let sent_email = app.sent_emails.last_to(&email).unwrap();
+    assert_eq!(sent_email.subject, "Your password was changed");
+}
+
+#[tokio::test]
+async fn should_end_other_sessions_only() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    let other_device = Device::log_in(&app, &body).await;
+    let current_token = Device::log_in(&app, &body).await.token;
+
+    let response = app
+        .post_change_password(
+            &json!({"currentPassword": "password", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_verify_token(&json!({"token": other_device.token}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = other_device.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 401);
+
+    // while the user stays logged in here
+    let response = app
+        .post_verify_token(&json!({"token": current_token}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_current_password() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    let response = app
+        .post_change_password(
+            &json!({"currentPassword": "wrong-password", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_too_many_incorrect_current_passwords() {
+    let app = TestApp::new().await;
+    let email = log_in(&app).await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_ACCOUNT {
+        let response = app
+            .post_change_password(
+                &json!({"currentPassword": "wrong-password", "newPassword": "new-password"}),
+            )
+            .await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app
+        .post_change_password(
+            &json!({"currentPassword": "password", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 429);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_new_password() {
+    let app = TestApp::new().await;
+    log_in(&app).await;
+
+    let response = app
+        .post_change_password(&json!({"currentPassword": "password", "newPassword": "short"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = app
+        .post_change_password(&json!({"currentPassword": "password", "newPassword": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
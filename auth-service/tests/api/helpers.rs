@@ -1,38 +1,90 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
+    app_state::{
+        AppState, BannedTokenStoreType, KeyringType, OneTimeTokenStoreType, RecoveryCodeStoreType,
+        SessionStoreType, TwoFACodeStoreType, UserStoreType,
+    },
+    domain::{
+        audit::{AuditEvent, AuditEventKind, AuditSink, AuditSinkError},
+        user::Email,
+        EmailClient, EmailClientResult,
+    },
     services::{
         hashmap_banned_token_store::HashmapBannedTokenStore,
+        hashmap_recovery_code_store::HashmapRecoveryCodeStore,
         hashmap_two_fa_code_store::HashmapTwoFACodeStore, hashmap_user_store::HashmapUserStore,
+        sqlite_recovery_code_store::SqliteRecoveryCodeStore, sqlite_user_store::SqliteUserStore,
     },
     utils::{
-        constants::{test, JWT_COOKIE_NAME},
+        constants::{test, JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+        settings::{AppSettings, JwtSettings, UserStoreBackend},
         ThreadSafe,
     },
     Application,
 };
-use reqwest::cookie::{Cookie, Jar};
+use reqwest::{
+    cookie::{Cookie, Jar},
+    header::{COOKIE, USER_AGENT},
+    Method,
+};
+use tempfile::TempDir;
+use tokio::sync::RwLock;
 use uuid::Uuid;
 
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub http_client: reqwest::Client,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub session_store: SessionStoreType,
+    pub one_time_token_store: OneTimeTokenStoreType,
+    pub sent_emails: SentEmails,
+    pub audit_events: AuditEvents,
+    pub keyring: KeyringType,
+    pub jwt_settings: JwtSettings,
+    // keeps the temporary SQLite database alive for as long as the app
+    _database_dir: Option<TempDir>,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
-        let user_store = HashmapUserStore::thread_safe();
-        let banned_token_store = HashmapBannedTokenStore::thread_safe();
-        let two_fa_code_store = HashmapTwoFACodeStore::thread_safe();
+        Self::with_state(|app_state| app_state).await
+    }
 
-        let app_state = AppState::default()
-            .user_store(user_store)
-            .banned_token_store(banned_token_store.clone())
-            .two_fa_code_store(two_fa_code_store.clone());
+    /// Builds the app after letting the test tweak its state (eg: swap a
+    /// store for a differently configured one). The handles exposed on the
+    /// `TestApp` point to whatever ends up in the final state.
+    pub async fn with_state(configure: impl FnOnce(AppState) -> AppState) -> Self {
+        let (user_store, recovery_code_store, database_dir) = build_persistent_stores().await;
+        let sent_emails = SentEmails::default();
+        let audit_events = AuditEvents::default();
+
+        let app_state = configure(
+            AppState::default()
+                .user_store(user_store)
+                .recovery_code_store(recovery_code_store)
+                .banned_token_store(HashmapBannedTokenStore::thread_safe())
+                .two_fa_code_store(HashmapTwoFACodeStore::thread_safe())
+                .email_client(Arc::new(RwLock::new(TestEmailClient {
+                    sent_emails: sent_emails.clone(),
+                })))
+                .audit_sink(Arc::new(RwLock::new(TestAuditSink {
+                    audit_events: audit_events.clone(),
+                }))),
+        );
+
+        let user_store = app_state.user_store.clone();
+        let banned_token_store = app_state.banned_token_store.clone();
+        let two_fa_code_store = app_state.two_fa_code_store.clone();
+        let recovery_code_store = app_state.recovery_code_store.clone();
+        let session_store = app_state.session_store.clone();
+        let one_time_token_store = app_state.one_time_token_store.clone();
+        let keyring = app_state.keyring.clone();
+        let jwt_settings = app_state.settings.jwt.clone();
 
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
@@ -53,25 +105,65 @@ impl TestApp {
             address,
             cookie_jar,
             http_client,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            recovery_code_store,
+            session_store,
+            one_time_token_store,
+            sent_emails,
+            audit_events,
+            keyring,
+            jwt_settings,
+            _database_dir: database_dir,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_signing_key(&self, admin_api_key: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/keys/rotate", &self.address));
+        if let Some(admin_api_key) = admin_api_key {
+            request = request.bearer_auth(admin_api_key);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_introspect(&self, token: &str, api_key: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", &self.address))
+            .form(&[("token", token)]);
+        if let Some(api_key) = api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -83,7 +175,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -98,6 +190,110 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{id}", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .delete(format!("{}/account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -110,6 +306,42 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_totp_enroll<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -123,21 +355,230 @@ impl TestApp {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+impl SentEmail {
+    /// The one-time token (64 hex characters) found in the content, if any.
+    pub fn one_time_token(&self) -> Option<String> {
+        self.content
+            .split(|c: char| !c.is_ascii_hexdigit())
+            .find(|word| word.len() == 64)
+            .map(str::to_owned)
+    }
+}
+
+/// Emails sent by the app under test, oldest first.
+#[derive(Clone, Default)]
+pub struct SentEmails(Arc<Mutex<Vec<SentEmail>>>);
+
+impl SentEmails {
+    pub fn to(&self, recipient: &str) -> Vec<SentEmail> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|email| email.recipient == recipient)
+            .cloned()
+            .collect()
+    }
+
+    pub fn last_to(&self, recipient: &str) -> Option<SentEmail> {
+        self.to(recipient).pop()
+    }
+
+    /// First email sent to `recipient` after the `sent_before` ones, waiting
+    /// a bit for it when sent off the request path.
+    pub async fn wait_for_next(&self, recipient: &str, sent_before: usize) -> Option<SentEmail> {
+        for _ in 0..100 {
+            if let Some(sent_email) = self.to(recipient).into_iter().nth(sent_before) {
+                return Some(sent_email);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        None
+    }
+}
+
+// Captures emails instead of sending them, so tests can read their content.
+struct TestEmailClient {
+    sent_emails: SentEmails,
+}
+
+#[async_trait::async_trait]
+impl EmailClient for TestEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> EmailClientResult<()> {
+        self.sent_emails.0.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+
+        Ok(())
+    }
+}
+
+/// Events recorded by the app under test, oldest first.
+#[derive(Clone, Default)]
+pub struct AuditEvents(Arc<Mutex<Vec<AuditEvent>>>);
+
+impl AuditEvents {
+    /// Kinds of the events about `email`.
+    pub fn kinds_for(&self, email: &str) -> Vec<AuditEventKind> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.email.as_deref() == Some(email))
+            .map(|event| event.kind)
+            .collect()
+    }
+
+    pub fn last(&self) -> Option<AuditEvent> {
+        self.0.lock().unwrap().last().cloned()
+    }
+}
+
+// Keeps events in memory, so tests can check what got recorded.
+struct TestAuditSink {
+    audit_events: AuditEvents,
+}
+
+#[async_trait::async_trait]
+impl AuditSink for TestAuditSink {
+    async fn record(&mut self, event: &AuditEvent) -> Result<(), AuditSinkError> {
+        self.audit_events.0.lock().unwrap().push(event.clone());
+
+        Ok(())
+    }
+}
+
+// The suite runs against the in-memory stores by default. Set `USER_STORE=sqlite`
+// to run it against a SQLite database in a temporary directory instead.
+async fn build_persistent_stores() -> (UserStoreType, RecoveryCodeStoreType, Option<TempDir>) {
+    match AppSettings::from_env().user_store {
+        UserStoreBackend::InMemory => (
+            HashmapUserStore::thread_safe(),
+            HashmapRecoveryCodeStore::thread_safe(),
+            None,
+        ),
+        UserStoreBackend::Sqlite { .. } => {
+            let dir = TempDir::new().expect("Failed to create temporary directory");
+            let url = format!("sqlite://{}", dir.path().join("auth.db").display());
+            let store = SqliteUserStore::connect(&url)
+                .await
+                .expect("Failed to open SQLite user store");
+            let recovery_code_store = SqliteRecoveryCodeStore::new(store.pool());
+
+            (
+                Arc::new(RwLock::new(store)),
+                Arc::new(RwLock::new(recovery_code_store)),
+                Some(dir),
+            )
+        }
+    }
+}
+
+// Cookies of another device the user logged in from: the JWT and the refresh
+// token.
+pub struct Device {
+    pub token: String,
+    pub refresh_token: String,
+    user_agent: Option<String>,
+}
+
+impl Device {
+    pub async fn log_in<Body: serde::Serialize>(app: &TestApp, body: &Body) -> Self {
+        let response = app.post_login(body).await;
+        assert_eq!(response.status_code(), 200);
+
+        Self::from_login_response(&response, None)
+    }
+
+    /// Logs in from a client that identifies itself with `user_agent`.
+    pub async fn log_in_as<Body: serde::Serialize>(
+        app: &TestApp,
+        body: &Body,
+        user_agent: &str,
+    ) -> Self {
+        let response = reqwest::Client::new()
+            .post(format!("{}/login", &app.address))
+            .header(USER_AGENT, user_agent)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status_code(), 200);
+
+        Self::from_login_response(&response, Some(user_agent.to_owned()))
+    }
+
+    fn from_login_response(response: &reqwest::Response, user_agent: Option<String>) -> Self {
+        Self {
+            token: response.get_auth_cookie().unwrap().value().to_owned(),
+            refresh_token: response.get_refresh_cookie().unwrap().value().to_owned(),
+            user_agent,
+        }
+    }
+
+    pub async fn get(&self, app: &TestApp, path: &str) -> reqwest::Response {
+        self.request(Method::GET, app, path).await
+    }
+
+    pub async fn post(&self, app: &TestApp, path: &str) -> reqwest::Response {
+        self.request(Method::POST, app, path).await
+    }
+
+    // A client of its own, so as not to share cookies with the app's one
+    async fn request(&self, method: Method, app: &TestApp, path: &str) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .request(method, format!("{}{path}", &app.address))
+            .header(
+                COOKIE,
+                format!(
+                    "{JWT_COOKIE_NAME}={}; {REFRESH_TOKEN_COOKIE_NAME}={}",
+                    self.token, self.refresh_token
+                ),
+            );
+        if let Some(user_agent) = &self.user_agent {
+            request = request.header(USER_AGENT, user_agent);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
 pub trait ResponseExt {
-    fn get_auth_cookie(&self) -> Option<Cookie>;
+    fn get_auth_cookie(&self) -> Option<Cookie<'_>>;
+    fn get_refresh_cookie(&self) -> Option<Cookie<'_>>;
     fn status_code(&self) -> u16;
 }
 
 impl ResponseExt for reqwest::Response {
-    fn get_auth_cookie(&self) -> Option<Cookie> {
+    fn get_auth_cookie(&self) -> Option<Cookie<'_>> {
         self.cookies()
             .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
     }
 
+    fn get_refresh_cookie(&self) -> Option<Cookie<'_>> {
+        self.cookies()
+            .find(|cookie| cookie.name() == REFRESH_TOKEN_COOKIE_NAME)
+    }
+
     fn status_code(&self) -> u16 {
         self.status().as_u16()
     }
@@ -0,0 +1,134 @@
+use auth_service::{
+    domain::{data_stores::session::SessionId, user::Email},
+    routes::IntrospectResponse,
+    utils::{auth::generate_auth_cookie, settings::AppSettings},
+};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+const INTROSPECTION_API_KEY: &str = "introspection-api-key";
+
+async fn app_with_introspection_api_key() -> TestApp {
+    TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            introspection_api_key: Some(INTROSPECTION_API_KEY.to_owned()),
+            ..AppSettings::default()
+        })
+    })
+    .await
+}
+
+async fn generate_token(app: &TestApp, email: &str, two_fa: bool) -> String {
+    let cookie = generate_auth_cookie(
+        &Email::parse(email).unwrap(),
+        &SessionId::default(),
+        two_fa,
+        &app.keyring.read().await.active(),
+        &app.jwt_settings,
+    )
+    .unwrap();
+
+    cookie.value().to_owned()
+}
+
+#[tokio::test]
+async fn should_return_400_if_api_key_is_missing() {
+    let app = app_with_introspection_api_key().await;
+    let token = generate_token(&app, &get_random_email(), false).await;
+
+    let response = app.post_introspect(&token, None).await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_api_key_is_incorrect() {
+    let app = app_with_introspection_api_key().await;
+    let token = generate_token(&app, &get_random_email(), false).await;
+
+    let response = app.post_introspect(&token, Some("wrong-key")).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_no_api_key_is_configured() {
+    let app = TestApp::new().await;
+    let token = generate_token(&app, &get_random_email(), false).await;
+
+    let response = app
+        .post_introspect(&token, Some(INTROSPECTION_API_KEY))
+        .await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_claims_of_active_token() {
+    let app = app_with_introspection_api_key().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+
+    let response = app
+        .post_introspect(&token, Some(INTROSPECTION_API_KEY))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let introspection = response.json::<IntrospectResponse>().await.unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, Some(email));
+    assert_eq!(introspection.iss, Some(app.jwt_settings.issuer.clone()));
+    assert_eq!(introspection.aud, Some(app.jwt_settings.audience.clone()));
+    assert_eq!(introspection.token_type.as_deref(), Some("Bearer"));
+    assert_eq!(introspection.two_fa, Some(false));
+    assert!(introspection.jti.is_some());
+    assert!(introspection.exp > introspection.iat);
+}
+
+#[tokio::test]
+async fn should_report_second_factor() {
+    let app = app_with_introspection_api_key().await;
+    let token = generate_token(&app, &get_random_email(), true).await;
+
+    let response = app
+        .post_introspect(&token, Some(INTROSPECTION_API_KEY))
+        .await;
+    let introspection = response.json::<IntrospectResponse>().await.unwrap();
+
+    assert!(introspection.active);
+    assert_eq!(introspection.two_fa, Some(true));
+}
+
+#[tokio::test]
+async fn should_only_return_active_false_for_invalid_token() {
+    let app = app_with_introspection_api_key().await;
+
+    let response = app
+        .post_introspect("invalid", Some(INTROSPECTION_API_KEY))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        json!({"active": false})
+    );
+}
+
+#[tokio::test]
+async fn should_return_inactive_for_revoked_token() {
+    let app = app_with_introspection_api_key().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+    app.post_logout().await;
+
+    let response = app
+        .post_introspect(&token, Some(INTROSPECTION_API_KEY))
+        .await;
+    let introspection = response.json::<IntrospectResponse>().await.unwrap();
+
+    assert_eq!(introspection, IntrospectResponse::default());
+}
@@ -0,0 +1,47 @@
+use auth_service::utils::auth::Claims;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+#[tokio::test]
+async fn should_publish_signing_key() {
+    let app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status_code(), 200);
+
+    let jwks = response.json::<JwkSet>().await.unwrap();
+    assert_eq!(jwks.keys.len(), 1);
+    assert_eq!(
+        jwks.keys[0].common.key_id.as_deref(),
+        Some(app.keyring.read().await.active().kid())
+    );
+}
+
+#[tokio::test]
+async fn should_verify_issued_tokens_with_published_key() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+
+    let jwks = app.get_jwks().await.json::<JwkSet>().await.unwrap();
+
+    // what any other service would do, without sharing any secret with us
+    let header = decode_header(&token).unwrap();
+    assert_eq!(header.alg, Algorithm::EdDSA);
+
+    let jwk = jwks.find(&header.kid.unwrap()).expect("Unknown kid");
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_issuer(&[&app.jwt_settings.issuer]);
+    validation.set_audience(&[&app.jwt_settings.audience]);
+    let claims = decode::<Claims>(&token, &DecodingKey::from_jwk(jwk).unwrap(), &validation)
+        .unwrap()
+        .claims;
+
+    assert_eq!(claims.sub, email);
+}
@@ -1,8 +1,21 @@
+use std::{sync::Arc, time::Instant};
+
 use auth_service::{
-    domain::{data_stores::twofa::LoginAttemptId, user::Email},
+    domain::{
+        data_stores::{
+            failed_login::{LockoutPolicy, MAX_FAILED_LOGINS_PER_ACCOUNT},
+            twofa::LoginAttemptId,
+        },
+        user::{Email, HashedPassword, Password, PasswordHashingParams, User},
+    },
     routes::TwoFactorAuthResponse,
+    services::hashmap_failed_login_store::HashmapFailedLoginStore,
+    utils::settings::AppSettings,
 };
+use chrono::Duration;
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
 use serde_json::json;
+use tokio::sync::RwLock;
 
 use crate::helpers::{get_random_email, ResponseExt, TestApp};
 
@@ -47,6 +60,50 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(response.status_code(), 401);
 }
 
+#[tokio::test]
+async fn should_not_tell_unknown_users_from_wrong_passwords() {
+    let app = TestApp::new().await;
+    let login_body = sign_up(&app).await;
+    let unknown_user_body = json!({"email": get_random_email(), "password": "password"});
+
+    // (status, content type, body) of the response, and how long it took at best
+    async fn sample(app: &TestApp, body: &serde_json::Value) -> ((u16, String, String), Duration) {
+        let mut fastest = Duration::max_value();
+        let mut last_response = None;
+        for _ in 0..3 {
+            let start = Instant::now();
+            let response = app.post_login(body).await;
+            fastest = fastest.min(Duration::from_std(start.elapsed()).unwrap());
+
+            assert!(response.get_auth_cookie().is_none());
+            let content_type = response.headers()[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            last_response = Some((
+                response.status_code(),
+                content_type,
+                response.text().await.unwrap(),
+            ));
+        }
+
+        (last_response.unwrap(), fastest)
+    }
+
+    let (wrong_password, wrong_password_time) =
+        sample(&app, &with_wrong_password(&login_body)).await;
+    let (unknown_user, unknown_user_time) = sample(&app, &unknown_user_body).await;
+
+    assert_eq!(wrong_password.0, 401);
+    assert_eq!(unknown_user, wrong_password);
+    // without a dummy hash to check, unknown users would be answered right
+    // away, orders of magnitude faster
+    assert!(
+        unknown_user_time * 4 > wrong_password_time,
+        "{unknown_user_time} vs {wrong_password_time}"
+    );
+}
+
 #[tokio::test]
 async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     let app = TestApp::new().await;
@@ -113,3 +170,215 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(stored_login_attempt_id, returned_login_attempt_id)
 }
+
+#[tokio::test]
+async fn should_rehash_password_if_hashing_params_changed() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password123").unwrap();
+    let outdated_params = PasswordHashingParams {
+        memory_cost: 8192,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    let outdated_hash = HashedPassword::parse(password.clone(), outdated_params)
+        .await
+        .unwrap();
+    app.user_store
+        .write()
+        .await
+        .add_user(User::new(email.clone(), outdated_hash.clone(), false))
+        .await
+        .unwrap();
+
+    let login_body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 200);
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(email.clone())
+        .await
+        .unwrap();
+    assert_ne!(user.password, outdated_hash);
+    assert!(user.password.needs_rehash(outdated_params));
+    assert!(!user.password.needs_rehash(PasswordHashingParams::default()));
+
+    // the upgraded hash must still accept the same password
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+async fn app_locking_after(account_policy: LockoutPolicy, ip_policy: LockoutPolicy) -> TestApp {
+    TestApp::with_state(|state| {
+        state
+            .failed_login_store(Arc::new(RwLock::new(HashmapFailedLoginStore::new(
+                account_policy,
+                ip_policy,
+            ))))
+            .settings(AppSettings {
+                trusted_proxy_hops: 1,
+                ..AppSettings::default()
+            })
+    })
+    .await
+}
+
+async fn post_login_from(
+    app: &TestApp,
+    forwarded_for: &str,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// Signs up a user without 2FA and returns the body logging them in.
+async fn sign_up(app: &TestApp) -> serde_json::Value {
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    body
+}
+
+fn with_wrong_password(login_body: &serde_json::Value) -> serde_json::Value {
+    json!({"email": login_body["email"], "password": "wrong-password"})
+}
+
+fn retry_after(response: &reqwest::Response) -> u64 {
+    response.headers()[RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_too_many_failures() {
+    let app = TestApp::new().await;
+    let login_body = sign_up(&app).await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_ACCOUNT {
+        let response = app.post_login(&with_wrong_password(&login_body)).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    // even the right password is refused for a while
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 429);
+    assert!(response.get_auth_cookie().is_none());
+
+    let retry_after = retry_after(&response);
+    let base_lockout = LockoutPolicy::per_account().base_lockout.num_seconds() as u64;
+    assert!(retry_after > 0 && retry_after <= base_lockout);
+}
+
+#[tokio::test]
+async fn should_lock_unknown_accounts_the_same_way() {
+    let app = TestApp::new().await;
+    let login_body = json!({"email": get_random_email(), "password": "password"});
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_ACCOUNT {
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_reset_failures_on_successful_login() {
+    let app = TestApp::new().await;
+    let login_body = sign_up(&app).await;
+
+    for _ in 0..2 {
+        for _ in 1..MAX_FAILED_LOGINS_PER_ACCOUNT {
+            app.post_login(&with_wrong_password(&login_body)).await;
+        }
+
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status_code(), 200);
+    }
+}
+
+#[tokio::test]
+async fn should_unlock_once_the_lockout_is_over() {
+    let policy = LockoutPolicy {
+        base_lockout: Duration::seconds(1),
+        ..LockoutPolicy::new(1)
+    };
+    let app = app_locking_after(policy, LockoutPolicy::per_ip()).await;
+    let login_body = sign_up(&app).await;
+
+    app.post_login(&with_wrong_password(&login_body)).await;
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 429);
+    assert_eq!(retry_after(&response), 1);
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_lock_the_client_address_after_too_many_failures() {
+    let app = app_locking_after(LockoutPolicy::per_account(), LockoutPolicy::new(3)).await;
+
+    // a few guesses on each of many accounts
+    for _ in 0..3 {
+        let login_body = sign_up(&app).await;
+        app.post_login(&with_wrong_password(&login_body)).await;
+    }
+
+    let login_body = sign_up(&app).await;
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_keep_counting_the_client_address_after_a_successful_login() {
+    let app = app_locking_after(LockoutPolicy::per_account(), LockoutPolicy::new(3)).await;
+
+    // logging into an account of their own doesn't clear an attacker's address
+    let own_login_body = sign_up(&app).await;
+    for _ in 0..2 {
+        let login_body = sign_up(&app).await;
+        app.post_login(&with_wrong_password(&login_body)).await;
+        let response = app.post_login(&own_login_body).await;
+        assert_eq!(response.status_code(), 200);
+    }
+
+    let login_body = sign_up(&app).await;
+    app.post_login(&with_wrong_password(&login_body)).await;
+    let response = app.post_login(&own_login_body).await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_lock_ipv6_clients_by_network() {
+    let app = app_locking_after(LockoutPolicy::per_account(), LockoutPolicy::new(3)).await;
+
+    // a new address of the same /64 for each guess
+    for i in 1..=3 {
+        let login_body = sign_up(&app).await;
+        let address = format!("2001:db8:1:2::{i}");
+        post_login_from(&app, &address, &with_wrong_password(&login_body)).await;
+    }
+
+    let login_body = sign_up(&app).await;
+    let response = post_login_from(&app, "2001:db8:1:2::4", &login_body).await;
+    assert_eq!(response.status_code(), 429);
+    let response = post_login_from(&app, "2001:db8:1:3::1", &login_body).await;
+    assert_eq!(response.status_code(), 200);
+}
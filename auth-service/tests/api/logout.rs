@@ -1,6 +1,11 @@
 use auth_service::{
-    domain::user::Email,
-    utils::{auth::generate_auth_cookie, constants::JWT_COOKIE_NAME},
+    domain::{data_stores::session::SessionId, user::Email},
+    services::hashmap_banned_token_store::HashmapBannedTokenStore,
+    utils::{
+        auth::{generate_auth_cookie, validate_token},
+        constants::JWT_COOKIE_NAME,
+        ThreadSafe,
+    },
 };
 use reqwest::Url;
 
@@ -36,7 +41,14 @@ async fn should_return_401_if_invalid_token() {
 async fn should_return_200_if_valid_jwt_cookie() {
     let app = TestApp::new().await;
     let random_email = Email::parse(get_random_email()).unwrap();
-    let cookie = generate_auth_cookie(&random_email).unwrap();
+    let cookie = generate_auth_cookie(
+        &random_email,
+        &SessionId::default(),
+        false,
+        &app.keyring.read().await.active(),
+        &app.jwt_settings,
+    )
+    .unwrap();
     app.cookie_jar.add_cookie_str(
         &cookie.to_string(),
         &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
@@ -45,17 +57,40 @@ async fn should_return_200_if_valid_jwt_cookie() {
     let response = app.post_logout().await;
     assert_eq!(response.status_code(), 200);
 
-    let token = cookie.value().to_string();
-    let banned_token_state = app.banned_token_store.read().await.verify(&token);
+    let claims = validate_token(
+        cookie.value(),
+        app.keyring.clone(),
+        &app.jwt_settings,
+        HashmapBannedTokenStore::thread_safe(),
+    )
+    .await
+    .unwrap();
+    let banned_token_state = app
+        .banned_token_store
+        .read()
+        .await
+        .verify(&claims.jti)
+        .await
+        .unwrap();
     assert!(banned_token_state.exists());
-    assert_eq!(banned_token_state.email().unwrap(), random_email)
+    assert_eq!(banned_token_state.email().unwrap(), random_email);
+
+    let banned_tokens = app.banned_token_store.read().await.len().await.unwrap();
+    assert_eq!(banned_tokens, 1);
 }
 
 #[tokio::test]
 async fn should_return_400_if_logout_called_twice_in_a_row() {
     let app = TestApp::new().await;
     let random_email = get_random_email();
-    let cookie = generate_auth_cookie(&Email::parse(random_email).unwrap()).unwrap();
+    let cookie = generate_auth_cookie(
+        &Email::parse(random_email).unwrap(),
+        &SessionId::default(),
+        false,
+        &app.keyring.read().await.active(),
+        &app.jwt_settings,
+    )
+    .unwrap();
     app.cookie_jar.add_cookie_str(
         &cookie.to_string(),
         &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
@@ -0,0 +1,100 @@
+use auth_service::{domain::user::PasswordHashingParams, utils::settings::AppSettings};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, Device, ResponseExt, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_reject_tokens_of_other_devices() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let other_device = Device::log_in(&app, &body).await;
+    // logged in from the app's client as well
+    Device::log_in(&app, &body).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status_code(), 200);
+
+    let auth_cookie = response.get_auth_cookie().unwrap();
+    assert!(auth_cookie.value().is_empty());
+
+    let response = other_device.post(&app, "/logout").await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app
+        .post_verify_token(&json!({"token": other_device.token}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    // nor can they get a new JWT
+    let response = other_device.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_not_affect_other_users() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    let other_body =
+        json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_signup(&other_body).await;
+
+    let other_user = Device::log_in(&app, &other_body).await;
+    Device::log_in(&app, &body).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_verify_token(&json!({"token": other_user.token}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = other_user.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_accept_tokens_issued_right_after() {
+    // cheap hashing, so that logging in again takes well under a second
+    let app = TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            password_hashing: PasswordHashingParams {
+                memory_cost: 8,
+                iterations: 1,
+                parallelism: 1,
+            },
+            ..AppSettings::default()
+        })
+    })
+    .await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    for _ in 0..3 {
+        let response = app.post_login(&body).await;
+        assert_eq!(response.status_code(), 200);
+        let response = app.post_logout_all().await;
+        assert_eq!(response.status_code(), 200);
+
+        // most likely within the same second as the logout
+        let response = app.post_login(&body).await;
+        assert_eq!(response.status_code(), 200);
+        let token = response.get_auth_cookie().unwrap().value().to_owned();
+
+        let response = app.post_verify_token(&json!({"token": token})).await;
+        assert_eq!(response.status_code(), 200);
+        let response = app.get_sessions().await;
+        assert_eq!(response.status_code(), 200);
+    }
+}
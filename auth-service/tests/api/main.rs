@@ -1,7 +1,22 @@
+mod account;
+mod admin;
+mod audit;
+mod change_password;
 mod helpers;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod logout_all;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
+mod request_id;
 mod root;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
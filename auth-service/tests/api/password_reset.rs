@@ -0,0 +1,214 @@
+use auth_service::domain::{
+    data_stores::one_time::{OneTimeToken, OneTimeTokenPurpose},
+    user::Email,
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, Device, ResponseExt, TestApp};
+
+async fn sign_up(app: &TestApp) -> String {
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    email
+}
+
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    let sent_before = app.sent_emails.to(email).len();
+    let response = app
+        .post_password_reset_request(&json!({"email": email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    app.sent_emails
+        .wait_for_next(email, sent_before)
+        .await
+        .and_then(|sent_email| sent_email.one_time_token())
+        .expect("No password reset token was emailed")
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_password_reset_request(&json!({})).await;
+    assert_eq!(response.status_code(), 422);
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": "token"}))
+        .await;
+    assert_eq!(response.status_code(), 422);
+}
+
+#[tokio::test]
+async fn should_email_a_reset_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+
+    let token = request_reset_token(&app, &email).await;
+
+    let sent_email = app.sent_emails.last_to(&email).unwrap();
+    assert_eq!(sent_email.subject, "Password reset request");
+    assert_eq!(sent_email.one_time_token(), Some(token));
+}
+
+#[tokio::test]
+async fn should_return_200_without_sending_anything_if_unknown_email() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_password_reset_request(&json!({"email": email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    assert!(app.sent_emails.to(&email).is_empty());
+}
+
+#[tokio::test]
+async fn should_reset_the_password() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    // the sessions ended by the reset don't take the new one with them
+    let token = response.get_auth_cookie().unwrap().value().to_owned();
+    let response = app.post_verify_token(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_end_all_sessions() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let device = Device::log_in(&app, &json!({"email": email, "password": "password"})).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app.post_verify_token(&json!({"token": device.token})).await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = device.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_accept_a_token_only_once() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "other-password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_only_accept_the_latest_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let old_token = request_reset_token(&app, &email).await;
+    let new_token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": old_token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": new_token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_unknown_or_expired_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": OneTimeToken::generate().as_ref(),
+            "newPassword": "new-password"
+        }))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let expired_token = OneTimeToken::generate();
+    app.one_time_token_store
+        .write()
+        .await
+        .add_token(
+            &Email::parse(&email).unwrap(),
+            OneTimeTokenPurpose::PasswordReset,
+            &expired_token,
+            Utc::now() - Duration::seconds(1),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": expired_token.as_ref(),
+            "newPassword": "new-password"
+        }))
+        .await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_request(&json!({"email": "not-an-email"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = app
+        .post_password_reset_confirm(
+            &json!({"token": "not-a-token", "newPassword": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "short"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    // the token was not used up by the rejected attempt
+    let response = app
+        .post_password_reset_confirm(&json!({"token": token, "newPassword": "new-password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
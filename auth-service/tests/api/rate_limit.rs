@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use auth_service::utils::settings::{AppSettings, RateLimit};
+use reqwest::header::RETRY_AFTER;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+async fn app_limiting_signup(limit: RateLimit, trusted_proxy_hops: usize) -> TestApp {
+    TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            rate_limits: HashMap::from([("/signup".to_owned(), limit)]),
+            trusted_proxy_hops,
+            ..AppSettings::default()
+        })
+    })
+    .await
+}
+
+async fn post_signup_from(app: &TestApp, forwarded_for: &str) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/signup", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .json(&signup_body())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn signup_body() -> serde_json::Value {
+    json!({"email": get_random_email(), "password": "password", "requires2FA": false})
+}
+
+fn header(response: &reqwest::Response, name: &str) -> u64 {
+    response.headers()[name].to_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn should_return_429_once_the_limit_is_reached() {
+    let app = app_limiting_signup(RateLimit::new(2, 60), 0).await;
+
+    for remaining in [1, 0] {
+        let response = app.post_signup(&signup_body()).await;
+        assert_eq!(response.status_code(), 201);
+        assert_eq!(header(&response, "RateLimit-Limit"), 2);
+        assert_eq!(header(&response, "RateLimit-Remaining"), remaining);
+        assert!(header(&response, "RateLimit-Reset") <= 60);
+    }
+
+    let response = app.post_signup(&signup_body()).await;
+    assert_eq!(response.status_code(), 429);
+    assert_eq!(header(&response, "RateLimit-Remaining"), 0);
+    let retry_after = header(&response, RETRY_AFTER.as_str());
+    assert!(retry_after > 0 && retry_after <= 30);
+}
+
+#[tokio::test]
+async fn should_not_limit_other_routes() {
+    let app = app_limiting_signup(RateLimit::new(1, 60), 0).await;
+    let body = signup_body();
+    app.post_signup(&body).await;
+
+    for _ in 0..3 {
+        let response = app.post_login(&body).await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.headers().get("RateLimit-Limit").is_none());
+    }
+}
+
+#[tokio::test]
+async fn should_limit_each_forwarded_client_separately_behind_a_proxy() {
+    let app = app_limiting_signup(RateLimit::new(1, 60), 1).await;
+
+    let response = post_signup_from(&app, "1.1.1.1").await;
+    assert_eq!(response.status_code(), 201);
+    let response = post_signup_from(&app, "1.1.1.1").await;
+    assert_eq!(response.status_code(), 429);
+
+    let response = post_signup_from(&app, "2.2.2.2").await;
+    assert_eq!(response.status_code(), 201);
+
+    // entries before the trusted hop are made up by the client
+    let response = post_signup_from(&app, "3.3.3.3, 1.1.1.1").await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_ignore_forwarded_for_without_trusted_proxy() {
+    let app = app_limiting_signup(RateLimit::new(1, 60), 0).await;
+
+    let response = post_signup_from(&app, "1.1.1.1").await;
+    assert_eq!(response.status_code(), 201);
+
+    let response = post_signup_from(&app, "2.2.2.2").await;
+    assert_eq!(response.status_code(), 429);
+}
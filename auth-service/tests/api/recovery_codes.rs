@@ -0,0 +1,212 @@
+use auth_service::{
+    domain::{
+        data_stores::{recovery::RECOVERY_CODES_COUNT, twofa::MAX_TWO_FA_ATTEMPTS},
+        user::{Email, Password},
+    },
+    routes::{RecoveryCodesResponse, SignupResponse, TwoFactorAuthResponse, Verify2FAResponse},
+};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+async fn signup_with_2fa(app: &TestApp) -> (Email, Password, Vec<String>) {
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+
+    let response = app
+        .post_signup(
+            &json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": true}),
+        )
+        .await;
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .unwrap()
+        .recovery_codes
+        .unwrap();
+
+    (email, password, recovery_codes)
+}
+
+async fn login(app: &TestApp, email: &Email, password: &Password) -> String {
+    let body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id
+}
+
+async fn login_with_recovery_code(
+    app: &TestApp,
+    email: &Email,
+    password: &Password,
+    recovery_code: &str,
+) -> reqwest::Response {
+    let login_attempt_id = login(app, email, password).await;
+    let body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "recoveryCode": recovery_code});
+
+    app.post_verify_2fa(&body).await
+}
+
+#[tokio::test]
+async fn should_login_with_recovery_code_and_report_remaining_codes() {
+    let app = TestApp::new().await;
+    let (email, password, recovery_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response.get_auth_cookie().is_some());
+
+    let response = response.json::<Verify2FAResponse>().await.unwrap();
+    assert_eq!(response.remaining_recovery_codes, RECOVERY_CODES_COUNT - 1);
+}
+
+#[tokio::test]
+async fn should_accept_recovery_code_only_once() {
+    let app = TestApp::new().await;
+    let (email, password, recovery_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[0]).await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[1]).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_count_wrong_recovery_codes_as_failed_attempts() {
+    let app = TestApp::new().await;
+    let (email, password, recovery_codes) = signup_with_2fa(&app).await;
+
+    let login_attempt_id = login(&app, &email, &password).await;
+    let wrong_body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "recoveryCode": "aaaaa-aaaaa"});
+
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app.post_verify_2fa(&wrong_body).await;
+    assert_eq!(response.status_code(), 429);
+
+    // the recovery codes themselves are untouched
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_unless_exactly_one_second_factor() {
+    let app = TestApp::new().await;
+    let (email, password, recovery_codes) = signup_with_2fa(&app).await;
+    let login_attempt_id = login(&app, &email, &password).await;
+
+    let bodies = [
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id}),
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": "123456", "recoveryCode": recovery_codes[0]}),
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "recoveryCode": "not a code"}),
+    ];
+
+    for body in bodies {
+        let response = app.post_verify_2fa(&body).await;
+        assert_eq!(response.status_code(), 400, "Failed for input: {body:?}");
+    }
+}
+
+#[tokio::test]
+async fn should_report_remaining_codes_when_using_2fa_code() {
+    let app = TestApp::new().await;
+    let (email, password, _) = signup_with_2fa(&app).await;
+    let login_attempt_id = login(&app, &email, &password).await;
+
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+
+    let body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": two_fa_code.as_ref()});
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = response.json::<Verify2FAResponse>().await.unwrap();
+    assert_eq!(response.remaining_recovery_codes, RECOVERY_CODES_COUNT);
+}
+
+#[tokio::test]
+async fn should_return_400_when_regenerating_without_token() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_recovery_codes(&json!({"password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_400_when_regenerating_without_2fa() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_recovery_codes(&json!({"password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_require_the_password_to_regenerate() {
+    let app = TestApp::new().await;
+    let (email, password, old_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &password, &old_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+
+    for (body, status_code) in [
+        (json!({}), 400),
+        (json!({"password": "wrong-password"}), 401),
+    ] {
+        let response = app.post_recovery_codes(&body).await;
+        assert_eq!(response.status_code(), status_code);
+    }
+
+    // the old codes still work
+    let response = login_with_recovery_code(&app, &email, &password, &old_codes[1]).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_regenerate_codes_and_invalidate_old_ones() {
+    let app = TestApp::new().await;
+    let (email, password, old_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &password, &old_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = app
+        .post_recovery_codes(&json!({"password": password.as_ref()}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let RecoveryCodesResponse { recovery_codes } = response.json().await.unwrap();
+    assert_eq!(recovery_codes.len(), RECOVERY_CODES_COUNT);
+
+    let response = login_with_recovery_code(&app, &email, &password, &old_codes[1]).await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = login_with_recovery_code(&app, &email, &password, &recovery_codes[0]).await;
+    assert_eq!(response.status_code(), 200);
+    let response = response.json::<Verify2FAResponse>().await.unwrap();
+    assert_eq!(response.remaining_recovery_codes, RECOVERY_CODES_COUNT - 1);
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use auth_service::{
+    services::hashmap_refresh_token_store::HashmapRefreshTokenStore,
+    utils::constants::REFRESH_TOKEN_COOKIE_NAME,
+};
+use chrono::Duration;
+use reqwest::Url;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+// Logs a new user in and returns the refresh token it was given.
+async fn login(app: &TestApp) -> String {
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned()
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!("{REFRESH_TOKEN_COOKIE_NAME}={token}; HttpOnly; SameSite=Lax; Path=/"),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_set_http_only_refresh_cookie_on_login() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let response = app.post_login(&body).await;
+    let refresh_cookie = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found");
+
+    assert!(!refresh_cookie.value().is_empty());
+    assert!(refresh_cookie.http_only());
+    assert!(refresh_cookie.max_age().is_some());
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_refresh_token() {
+    let app = TestApp::new().await;
+
+    set_refresh_cookie(&app, "invalid");
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+
+    set_refresh_cookie(&app, &"ab".repeat(32));
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_rotate_refresh_token_and_issue_new_jwt() {
+    let app = TestApp::new().await;
+    let refresh_token = login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let new_refresh_token = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+    assert_ne!(new_refresh_token, refresh_token);
+
+    // the rotated token can be refreshed in turn
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_revoke_family_when_used_token_is_replayed() {
+    let app = TestApp::new().await;
+    let stolen_token = login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 200);
+    let latest_token = response.get_refresh_cookie().unwrap().value().to_owned();
+
+    set_refresh_cookie(&app, &stolen_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+
+    // the legitimate, latest token of the family is revoked too
+    set_refresh_cookie(&app, &latest_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_expired() {
+    let app = TestApp::with_state(|state| {
+        state.refresh_token_store(Arc::new(RwLock::new(HashmapRefreshTokenStore::new(
+            Duration::zero(),
+        ))))
+    })
+    .await;
+    login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let app = TestApp::new().await;
+    let refresh_token = login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status_code(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 401);
+}
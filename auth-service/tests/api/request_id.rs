@@ -0,0 +1,34 @@
+use crate::helpers::{ResponseExt, TestApp};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[tokio::test]
+async fn should_give_each_request_an_id() {
+    let app = TestApp::new().await;
+
+    let first = app.get_jwks().await;
+    let second = app.get_jwks().await;
+    assert_eq!(first.status_code(), 200);
+
+    let first_id = first.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+    let second_id = second.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+    assert!(!first_id.is_empty());
+    assert_ne!(first_id, second_id);
+}
+
+#[tokio::test]
+async fn should_keep_the_id_a_request_came_with() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .header(REQUEST_ID_HEADER, "upstream-request-id")
+        .json(&serde_json::json!({"token": "invalid"}))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status_code(), 401);
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "upstream-request-id");
+}
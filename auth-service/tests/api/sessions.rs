@@ -0,0 +1,196 @@
+use auth_service::{domain::user::Email, routes::SessionsResponse};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, Device, ResponseExt, TestApp};
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status_code(), 200);
+
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = app.delete_session(&Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_the_user() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let other_device = Device::log_in_as(&app, &body, "other-device").await;
+    Device::log_in(&app, &body).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 2);
+
+    let other_session = sessions.iter().find(|session| !session.current).unwrap();
+    assert_eq!(other_session.user_agent.as_deref(), Some("other-device"));
+    assert_eq!(other_session.ip.as_deref(), Some("127.0.0.1"));
+
+    // the other device sees the same sessions, with its own as the current one
+    let response = other_device.get(&app, "/sessions").await;
+    assert_eq!(response.status_code(), 200);
+    let sessions = response.json::<SessionsResponse>().await.unwrap().sessions;
+    let current = sessions.iter().find(|session| session.current).unwrap();
+    assert_eq!(current.id, other_session.id);
+}
+
+#[tokio::test]
+async fn should_not_list_sessions_of_other_users() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    let other_body =
+        json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_signup(&other_body).await;
+
+    Device::log_in(&app, &other_body).await;
+    Device::log_in(&app, &body).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[tokio::test]
+async fn should_keep_the_session_when_refreshing() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_login(&body).await;
+
+    let before = get_sessions(&app).await.sessions;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status_code(), 200);
+
+    let after = get_sessions(&app).await.sessions;
+    assert_eq!(after.len(), 1);
+    assert_eq!(after[0].id, before[0].id);
+    assert!(after[0].current);
+}
+
+#[tokio::test]
+async fn should_revoke_another_session() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+
+    let other_device = Device::log_in(&app, &body).await;
+    Device::log_in(&app, &body).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    let other_session = sessions.iter().find(|session| !session.current).unwrap();
+
+    let response = app.delete_session(&other_session.id).await;
+    assert_eq!(response.status_code(), 200);
+
+    // the current session is left alone
+    assert!(response.get_auth_cookie().is_none());
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    // while the other device is logged out
+    let response = app
+        .post_verify_token(&json!({"token": other_device.token}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = other_device.post(&app, "/refresh").await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_log_out_when_revoking_the_current_session() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    let token = Device::log_in(&app, &body).await.token;
+
+    let sessions = get_sessions(&app).await.sessions;
+
+    let response = app.delete_session(&sessions[0].id).await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response.get_auth_cookie().unwrap().value().is_empty());
+    assert!(response.get_refresh_cookie().unwrap().value().is_empty());
+
+    let response = app.post_verify_token(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_404_if_session_not_found() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    let other_body =
+        json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_signup(&other_body).await;
+
+    let other_user = Device::log_in(&app, &other_body).await;
+    let response = other_user.get(&app, "/sessions").await;
+    let other_users_session = response.json::<SessionsResponse>().await.unwrap().sessions[0]
+        .id
+        .clone();
+
+    Device::log_in(&app, &body).await;
+
+    let response = app.delete_session(&Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status_code(), 404);
+
+    // one cannot revoke sessions of other users
+    let response = app.delete_session(&other_users_session).await;
+    assert_eq!(response.status_code(), 404);
+
+    let response = app
+        .post_verify_token(&json!({"token": other_user.token}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_session_id_is_malformed() {
+    let app = TestApp::new().await;
+    let body = json!({"email": get_random_email(), "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_login(&body).await;
+
+    let response = app.delete_session("not-a-session-id").await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_end_the_session_on_logout() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    app.post_signup(&body).await;
+    app.post_login(&body).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status_code(), 200);
+
+    let sessions = app
+        .session_store
+        .read()
+        .await
+        .get_sessions(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert!(sessions.is_empty());
+}
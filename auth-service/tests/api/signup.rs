@@ -1,4 +1,8 @@
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::{
+    domain::{data_stores::recovery::RECOVERY_CODES_COUNT, user::Email},
+    routes::SignupResponse,
+    ErrorResponse,
+};
 use serde_json::json;
 
 use crate::helpers::{get_random_email, ResponseExt, TestApp};
@@ -45,9 +49,10 @@ async fn should_return_422_if_malformed_input() {
 async fn should_return_201_if_valid_input() {
     let app = TestApp::new().await;
 
-    let body = json!({"email": "email@email.com", "password": "password", "requires2FA": true});
+    let body = json!({"email": "email@email.com", "password": "password", "requires2FA": false});
     let expected_response = SignupResponse {
         message: "User created successfully!".into(),
+        recovery_codes: None,
     };
 
     let response = app.post_signup(&body).await;
@@ -62,6 +67,33 @@ async fn should_return_201_if_valid_input() {
     )
 }
 
+#[tokio::test]
+async fn should_return_recovery_codes_if_2fa_required() {
+    let app = TestApp::new().await;
+
+    let body = json!({"email": "email@email.com", "password": "password", "requires2FA": true});
+    let response = app.post_signup(&body).await;
+    assert_eq!(response.status_code(), 201);
+
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .unwrap()
+        .recovery_codes
+        .expect("Recovery codes should be returned");
+    assert_eq!(recovery_codes.len(), RECOVERY_CODES_COUNT);
+
+    let email = Email::parse("email@email.com").unwrap();
+    assert_eq!(
+        app.recovery_code_store
+            .read()
+            .await
+            .remaining_codes(&email)
+            .await,
+        Ok(RECOVERY_CODES_COUNT)
+    );
+}
+
 #[tokio::test]
 async fn should_return_400_if_invalid_input() {
     let invalid_bodies = [
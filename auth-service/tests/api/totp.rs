@@ -0,0 +1,324 @@
+use std::sync::Arc;
+
+use auth_service::{
+    domain::{
+        audit::AuditEventKind,
+        data_stores::{
+            failed_login::MAX_FAILED_LOGINS_PER_ACCOUNT, recovery::RECOVERY_CODES_COUNT,
+        },
+        totp::TotpSecret,
+        user::{Email, Password, TwoFAMethod},
+    },
+    routes::{EnrollTotpResponse, RecoveryCodesResponse, TwoFactorAuthResponse},
+    services::mock_clock::MockClock,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+// Halfway through a 30s time step, so that ±30s lands in the neighbouring
+// steps and ±60s two steps away.
+fn start_time() -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_025, 0).unwrap()
+}
+
+async fn test_app() -> (TestApp, Arc<RwLock<MockClock>>) {
+    let clock = Arc::new(RwLock::new(MockClock::new(start_time())));
+    let app = TestApp::with_state(|state| state.clock(clock.clone())).await;
+
+    (app, clock)
+}
+
+async fn signup_and_login(app: &TestApp) -> (Email, Password) {
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+    let body =
+        json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": false});
+
+    app.post_signup(&body).await;
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 200);
+
+    (email, password)
+}
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_totp_enroll(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 200);
+
+    let EnrollTotpResponse { secret, .. } = response.json().await.unwrap();
+    TotpSecret::parse(secret).unwrap()
+}
+
+// Confirms with the code of the step before, leaving the current one usable.
+async fn enroll_and_confirm(app: &TestApp) -> TotpSecret {
+    let secret = enroll(app).await;
+    let code = secret
+        .generate_code(start_time() - Duration::try_seconds(30).unwrap())
+        .unwrap();
+
+    let response = app.post_totp_confirm(&json!({"code": code})).await;
+    assert_eq!(response.status_code(), 200);
+
+    secret
+}
+
+async fn login_with_totp(app: &TestApp, email: &Email, password: &Password) -> String {
+    let body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status_code(), 206);
+
+    let response = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    assert_eq!(response.two_fa_method, "totp");
+
+    response.login_attempt_id
+}
+
+#[tokio::test]
+async fn should_return_400_when_enrolling_without_token() {
+    let (app, _) = test_app().await;
+
+    let response = app.post_totp_enroll(&json!({"password": "password"})).await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_return_secret_and_otpauth_uri_on_enrollment() {
+    let (app, _) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+
+    let response = app
+        .post_totp_enroll(&json!({"password": password.as_ref()}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let EnrollTotpResponse {
+        secret,
+        otpauth_uri,
+    } = response.json().await.unwrap();
+    assert!(otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(otpauth_uri.contains(&format!("secret={secret}")));
+
+    // nothing changes until the enrollment is confirmed
+    let user = app.user_store.read().await.get_user(email).await.unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::None);
+    assert_eq!(user.pending_totp_secret.unwrap().as_ref(), secret);
+}
+
+#[tokio::test]
+async fn should_only_activate_totp_with_valid_code() {
+    let (app, _) = test_app().await;
+    let (email, _) = signup_and_login(&app).await;
+
+    let response = app.post_totp_confirm(&json!({"code": "123456"})).await;
+    assert_eq!(response.status_code(), 400);
+
+    let secret = enroll(&app).await;
+    let stale_code = secret
+        .generate_code(start_time() - Duration::try_minutes(5).unwrap())
+        .unwrap();
+    let response = app.post_totp_confirm(&json!({"code": stale_code})).await;
+    assert_eq!(response.status_code(), 401);
+
+    let code = secret.generate_code(start_time()).unwrap();
+    let response = app.post_totp_confirm(&json!({"code": code})).await;
+    assert_eq!(response.status_code(), 200);
+
+    let RecoveryCodesResponse { recovery_codes } = response.json().await.unwrap();
+    assert_eq!(recovery_codes.len(), RECOVERY_CODES_COUNT);
+    assert_eq!(
+        app.recovery_code_store
+            .read()
+            .await
+            .remaining_codes(&email)
+            .await,
+        Ok(RECOVERY_CODES_COUNT)
+    );
+
+    let user = app.user_store.read().await.get_user(email).await.unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Totp(secret));
+    assert_eq!(user.pending_totp_secret, None);
+}
+
+#[tokio::test]
+async fn should_record_totp_enablement() {
+    let (app, _) = test_app().await;
+    let (email, _) = signup_and_login(&app).await;
+
+    enroll(&app).await;
+    let response = app.post_totp_confirm(&json!({"code": "000000"})).await;
+    assert_eq!(response.status_code(), 401);
+    assert!(!app
+        .audit_events
+        .kinds_for(email.as_ref())
+        .contains(&AuditEventKind::TwoFAEnabled));
+
+    enroll_and_confirm(&app).await;
+    assert_eq!(
+        app.audit_events.kinds_for(email.as_ref()).last(),
+        Some(&AuditEventKind::TwoFAEnabled)
+    );
+}
+
+#[tokio::test]
+async fn should_require_the_password_to_enroll() {
+    let (app, _) = test_app().await;
+    let (email, _) = signup_and_login(&app).await;
+
+    for (body, status_code) in [
+        (json!({}), 400),
+        (json!({"code": "000000"}), 400),
+        (json!({"password": "wrong-password"}), 401),
+    ] {
+        let response = app.post_totp_enroll(&body).await;
+        assert_eq!(response.status_code(), status_code);
+    }
+
+    let user = app.user_store.read().await.get_user(email).await.unwrap();
+    assert_eq!(user.pending_totp_secret, None);
+}
+
+#[tokio::test]
+async fn should_require_reauthentication_to_replace_active_totp() {
+    let (app, _) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+
+    let response = app.post_totp_enroll(&json!({})).await;
+    assert_eq!(response.status_code(), 400);
+
+    for body in [
+        json!({"password": "wrong-password"}),
+        json!({"code": "000000"}),
+    ] {
+        let response = app.post_totp_enroll(&body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(email.clone())
+        .await
+        .unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Totp(secret.clone()));
+    assert_eq!(user.pending_totp_secret, None);
+
+    let code = secret.generate_code(start_time()).unwrap();
+    for body in [
+        json!({"password": password.as_ref()}),
+        json!({"code": code}),
+    ] {
+        let response = app.post_totp_enroll(&body).await;
+        assert_eq!(response.status_code(), 200);
+    }
+
+    // the code used to re-enroll is spent
+    let response = app.post_totp_enroll(&json!({"code": code})).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_too_many_incorrect_codes() {
+    let (app, _) = test_app().await;
+    let (_, password) = signup_and_login(&app).await;
+    enroll_and_confirm(&app).await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_ACCOUNT {
+        let response = app.post_totp_enroll(&json!({"code": "000000"})).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app
+        .post_totp_enroll(&json!({"password": password.as_ref()}))
+        .await;
+    assert_eq!(response.status_code(), 429);
+}
+
+#[tokio::test]
+async fn should_login_with_totp_code() {
+    let (app, _) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+
+    let login_attempt_id = login_with_totp(&app, &email, &password).await;
+
+    let code = secret.generate_code(start_time()).unwrap();
+    let body =
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": code});
+    let response = app.post_verify_2fa(&body).await;
+
+    assert_eq!(response.status_code(), 200);
+    assert!(response.get_auth_cookie().is_some());
+}
+
+#[tokio::test]
+async fn should_reject_emailed_code_for_totp_user() {
+    let (app, _) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+    enroll_and_confirm(&app).await;
+
+    let login_attempt_id = login_with_totp(&app, &email, &password).await;
+    let (_, stored_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+
+    let body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": stored_code.as_ref()});
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_accept_one_step_of_clock_skew() {
+    let (app, clock) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+    let step = Duration::try_seconds(30).unwrap();
+    let code = secret.generate_code(start_time()).unwrap();
+
+    let verify = |login_attempt_id: String, code: &str| json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": code});
+
+    clock.write().await.set(start_time() - step);
+    let login_attempt_id = login_with_totp(&app, &email, &password).await;
+    let response = app.post_verify_2fa(&verify(login_attempt_id, &code)).await;
+    assert_eq!(response.status_code(), 200);
+
+    // still within skew, but already used
+    clock.write().await.set(start_time() + step);
+    let login_attempt_id = login_with_totp(&app, &email, &password).await;
+    let response = app.post_verify_2fa(&verify(login_attempt_id, &code)).await;
+    assert_eq!(response.status_code(), 401);
+
+    let next_code = secret.generate_code(start_time() + step * 2).unwrap();
+    let login_attempt_id = login_with_totp(&app, &email, &password).await;
+    let response = app
+        .post_verify_2fa(&verify(login_attempt_id, &next_code))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_reject_two_steps_of_clock_skew() {
+    let (app, clock) = test_app().await;
+    let (email, password) = signup_and_login(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+    let step = Duration::try_seconds(30).unwrap();
+
+    for skew in [-step * 2, step * 2] {
+        clock.write().await.set(start_time() + skew);
+        let login_attempt_id = login_with_totp(&app, &email, &password).await;
+
+        let code = secret.generate_code(start_time()).unwrap();
+        let body =
+            json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": code});
+        let response = app.post_verify_2fa(&body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+}
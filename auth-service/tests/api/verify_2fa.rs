@@ -1,11 +1,17 @@
+use std::sync::Arc;
+
 use auth_service::{
     domain::{
-        data_stores::twofa::LoginAttemptId,
+        data_stores::twofa::{LoginAttemptId, MAX_TWO_FA_ATTEMPTS},
         user::{Email, Password},
     },
     routes::TwoFactorAuthResponse,
+    services::hashmap_two_fa_code_store::HashmapTwoFACodeStore,
+    utils::settings::AppSettings,
+    ErrorResponse,
 };
 use serde_json::json;
+use tokio::sync::RwLock;
 
 use crate::helpers::{get_random_email, ResponseExt, TestApp};
 
@@ -160,3 +166,162 @@ async fn should_return_401_if_same_code_twice() {
     let response = app.post_verify_2fa(&verify_2fa_body).await;
     assert_eq!(response.status_code(), 401);
 }
+
+#[tokio::test]
+async fn should_return_401_if_code_expired() {
+    let app = TestApp::with_state(|state| {
+        let store = HashmapTwoFACodeStore::new(chrono::Duration::zero(), MAX_TWO_FA_ATTEMPTS);
+        state.two_fa_code_store(Arc::new(RwLock::new(store)))
+    })
+    .await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+
+    app.post_signup(
+        &json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": true}),
+    )
+    .await;
+
+    let login_body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 206);
+
+    let TwoFactorAuthResponse {
+        login_attempt_id, ..
+    } = response.json::<TwoFactorAuthResponse>().await.unwrap();
+
+    let verify_2fa_body =
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": "123456"});
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status_code(), 401);
+
+    let error = response.json::<ErrorResponse>().await.unwrap().error;
+    assert!(error.contains("expired"));
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_incorrect_codes() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+
+    app.post_signup(
+        &json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": true}),
+    )
+    .await;
+
+    let login_body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 206);
+
+    let TwoFactorAuthResponse {
+        login_attempt_id, ..
+    } = response.json::<TwoFactorAuthResponse>().await.unwrap();
+
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+
+    let code: u32 = two_fa_code.as_ref().parse().unwrap();
+    let wrong_code = format!("{:06}", (code + 1) % 1_000_000);
+    let wrong_body =
+        json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": wrong_code});
+
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let response = app.post_verify_2fa(&wrong_body).await;
+    assert_eq!(response.status_code(), 429);
+
+    // the attempt is gone, even the right code is now refused
+    let verify_2fa_body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": two_fa_code.as_ref()});
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_not_count_attempts_with_unknown_login_attempt_id() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+
+    app.post_signup(
+        &json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": true}),
+    )
+    .await;
+
+    let login_body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&login_body).await;
+    let TwoFactorAuthResponse {
+        login_attempt_id, ..
+    } = response.json::<TwoFactorAuthResponse>().await.unwrap();
+
+    let forged_body = json!({"email": email.as_ref(), "loginAttemptId": LoginAttemptId::default().as_ref(), "2FACode": "123456"});
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&forged_body).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+
+    let verify_2fa_body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": two_fa_code.as_ref()});
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_accept_codes_of_configured_length() {
+    let app = TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            two_fa_code_length: 8,
+            ..AppSettings::default()
+        })
+    })
+    .await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let password = Password::parse("password").unwrap();
+
+    app.post_signup(
+        &json!({"email": email.as_ref(), "password": password.as_ref(), "requires2FA": true}),
+    )
+    .await;
+
+    let login_body = json!({"email": email.as_ref(), "password": password.as_ref()});
+    let response = app.post_login(&login_body).await;
+    let TwoFactorAuthResponse {
+        login_attempt_id, ..
+    } = response.json::<TwoFactorAuthResponse>().await.unwrap();
+
+    let (_, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .unwrap();
+    assert_eq!(two_fa_code.as_ref().len(), 8);
+
+    let six_digits_body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": &two_fa_code.as_ref()[..6]});
+    let response = app.post_verify_2fa(&six_digits_body).await;
+    assert_eq!(response.status_code(), 400);
+
+    let verify_2fa_body = json!({"email": email.as_ref(), "loginAttemptId": login_attempt_id, "2FACode": two_fa_code.as_ref()});
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status_code(), 200);
+}
@@ -0,0 +1,246 @@
+use auth_service::{
+    domain::{
+        data_stores::one_time::{OneTimeToken, OneTimeTokenPurpose},
+        user::Email,
+    },
+    utils::settings::AppSettings,
+};
+use reqwest::header::RETRY_AFTER;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, ResponseExt, TestApp};
+
+async fn app_requiring_verified_email() -> TestApp {
+    TestApp::with_state(|state| {
+        state.settings(AppSettings {
+            require_verified_email: true,
+            ..AppSettings::default()
+        })
+    })
+    .await
+}
+
+// Signs up a user and returns the token of the verification email sent.
+async fn sign_up(app: &TestApp, email: &str) -> String {
+    let body = json!({"email": email, "password": "password", "requires2FA": false});
+    let response = app.post_signup(&body).await;
+    assert_eq!(response.status_code(), 201);
+
+    let sent_email = app
+        .sent_emails
+        .last_to(email)
+        .expect("No verification email was sent");
+    assert_eq!(sent_email.subject, "Please verify your email address");
+
+    sent_email.one_time_token().unwrap()
+}
+
+async fn is_verified(app: &TestApp, email: &str) -> bool {
+    app.user_store
+        .read()
+        .await
+        .get_user(Email::parse(email).unwrap())
+        .await
+        .unwrap()
+        .verified
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_verify_email(&json!({})).await;
+    assert_eq!(response.status_code(), 422);
+
+    let response = app.post_resend_verification_email(&json!({})).await;
+    assert_eq!(response.status_code(), 422);
+}
+
+#[tokio::test]
+async fn should_verify_the_email_address() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = sign_up(&app, &email).await;
+    assert!(!is_verified(&app, &email).await);
+
+    let response = app.post_verify_email(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 200);
+    assert!(is_verified(&app, &email).await);
+
+    // the token can't be used twice
+    let response = app.post_verify_email(&json!({"token": token})).await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_unknown_token() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email).await;
+
+    let response = app
+        .post_verify_email(&json!({"token": OneTimeToken::generate().as_ref()}))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    // nor can a password reset token be used instead
+    let sent_before = app.sent_emails.to(&email).len();
+    app.post_password_reset_request(&json!({"email": email}))
+        .await;
+    let reset_token = app
+        .sent_emails
+        .wait_for_next(&email, sent_before)
+        .await
+        .and_then(|sent_email| sent_email.one_time_token())
+        .unwrap();
+
+    let response = app.post_verify_email(&json!({"token": reset_token})).await;
+    assert_eq!(response.status_code(), 401);
+    assert!(!is_verified(&app, &email).await);
+}
+
+#[tokio::test]
+async fn should_return_400_if_malformed_token() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_email(&json!({"token": "not-a-token"}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn should_let_unverified_users_log_in_by_default() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email).await;
+
+    let response = app
+        .post_login(&json!({"email": email, "password": "password"}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_refuse_unverified_users_if_required() {
+    let app = app_requiring_verified_email().await;
+    let email = get_random_email();
+    let token = sign_up(&app, &email).await;
+    let login_body = json!({"email": email, "password": "password"});
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 403);
+    assert!(response.get_auth_cookie().is_none());
+
+    app.post_verify_email(&json!({"token": token})).await;
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_throttle_resending_without_telling() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email).await;
+
+    // the email sent at signup is too recent, but the answer is the same as
+    // for any other email
+    let response = app
+        .post_resend_verification_email(&json!({"email": email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response.headers().get(RETRY_AFTER).is_none());
+
+    // giving the send, made off the request path, time to happen
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(app.sent_emails.to(&email).len(), 1);
+}
+
+#[tokio::test]
+async fn should_resend_a_new_token() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let old_token = sign_up(&app, &email).await;
+
+    // as if it had expired
+    app.one_time_token_store
+        .write()
+        .await
+        .consume_token(
+            OneTimeTokenPurpose::EmailVerification,
+            &OneTimeToken::parse(old_token.clone()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .post_resend_verification_email(&json!({"email": email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let new_token = app
+        .sent_emails
+        .wait_for_next(&email, 1)
+        .await
+        .and_then(|sent_email| sent_email.one_time_token())
+        .unwrap();
+    assert_ne!(new_token, old_token);
+
+    let response = app.post_verify_email(&json!({"token": new_token})).await;
+    assert_eq!(response.status_code(), 200);
+}
+
+#[tokio::test]
+async fn should_resend_once_to_concurrent_requests() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let old_token = sign_up(&app, &email).await;
+
+    // as if it had expired
+    app.one_time_token_store
+        .write()
+        .await
+        .consume_token(
+            OneTimeTokenPurpose::EmailVerification,
+            &OneTimeToken::parse(old_token).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = json!({"email": email});
+    let responses = tokio::join!(
+        app.post_resend_verification_email(&body),
+        app.post_resend_verification_email(&body),
+        app.post_resend_verification_email(&body),
+        app.post_resend_verification_email(&body),
+    );
+    for response in [responses.0, responses.1, responses.2, responses.3] {
+        assert_eq!(response.status_code(), 200);
+    }
+
+    assert!(app.sent_emails.wait_for_next(&email, 1).await.is_some());
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(app.sent_emails.to(&email).len(), 2);
+}
+
+#[tokio::test]
+async fn should_not_resend_to_unknown_or_verified_accounts() {
+    let app = TestApp::new().await;
+    let unknown_email = get_random_email();
+    let email = get_random_email();
+    let token = sign_up(&app, &email).await;
+    app.post_verify_email(&json!({"token": token})).await;
+
+    let response = app
+        .post_resend_verification_email(&json!({"email": unknown_email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert!(app.sent_emails.to(&unknown_email).is_empty());
+
+    let response = app
+        .post_resend_verification_email(&json!({"email": email}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(app.sent_emails.to(&email).len(), 1);
+}
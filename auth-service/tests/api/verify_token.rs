@@ -1,4 +1,7 @@
-use auth_service::{domain::user::Email, utils::auth::generate_auth_cookie};
+use auth_service::{
+    domain::{data_stores::session::SessionId, user::Email},
+    utils::{auth::generate_auth_cookie, keys::JwtSigningKey, settings::JwtSettings},
+};
 use serde_json::json;
 
 use crate::helpers::{get_random_email, ResponseExt, TestApp};
@@ -15,7 +18,14 @@ async fn should_return_422_if_malformed_input() {
 async fn should_return_200_valid_token() {
     let app = TestApp::new().await;
     let email = Email::parse("valid@email.com").unwrap();
-    let cookie = generate_auth_cookie(&email).unwrap();
+    let cookie = generate_auth_cookie(
+        &email,
+        &SessionId::default(),
+        false,
+        &app.keyring.read().await.active(),
+        &app.jwt_settings,
+    )
+    .unwrap();
     let token = cookie.value().to_owned();
 
     let response = app.post_verify_token(&json!({"token": token})).await;
@@ -23,6 +33,48 @@ async fn should_return_200_valid_token() {
     assert_eq!(response.status_code(), 200)
 }
 
+#[tokio::test]
+async fn should_return_401_if_signed_with_unknown_key() {
+    let app = TestApp::new().await;
+    let email = Email::parse("valid@email.com").unwrap();
+    let cookie = generate_auth_cookie(
+        &email,
+        &SessionId::default(),
+        false,
+        &JwtSigningKey::generate(),
+        &app.jwt_settings,
+    )
+    .unwrap();
+    let token = cookie.value().to_owned();
+
+    let response = app.post_verify_token(&json!({"token": token})).await;
+
+    assert_eq!(response.status_code(), 401)
+}
+
+#[tokio::test]
+async fn should_return_401_if_issued_for_another_audience() {
+    let app = TestApp::new().await;
+    let email = Email::parse("valid@email.com").unwrap();
+    let other_audience = JwtSettings {
+        audience: "another-service".to_owned(),
+        ..app.jwt_settings.clone()
+    };
+    let cookie = generate_auth_cookie(
+        &email,
+        &SessionId::default(),
+        false,
+        &app.keyring.read().await.active(),
+        &other_audience,
+    )
+    .unwrap();
+    let token = cookie.value().to_owned();
+
+    let response = app.post_verify_token(&json!({"token": token})).await;
+
+    assert_eq!(response.status_code(), 401)
+}
+
 #[tokio::test]
 async fn should_return_401_if_invalid_token() {
     let app = TestApp::new().await;
@@ -30,7 +82,9 @@ async fn should_return_401_if_invalid_token() {
         .post_verify_token(&json!({"token": "invalidtoken"}))
         .await;
 
-    assert_eq!(response.status_code(), 401)
+    assert_eq!(response.status_code(), 401);
+    // as before introspection existed: clients may not expect a body
+    assert_eq!(response.text().await.unwrap(), "");
 }
 
 #[tokio::test]